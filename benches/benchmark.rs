@@ -1,4 +1,4 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 
 #[rustfmt::skip]
 mod input;
@@ -6,6 +6,11 @@ mod input;
 #[global_allocator]
 static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
+/// Collects into the same map type as the `_proto`.
+fn collect_like<M: FromIterator<(u32, u32)>>(_proto: &M, keys: &[u32]) -> M {
+    keys.iter().map(|&n| (n, n)).collect()
+}
+
 fn benchmark_input(criterion: &mut Criterion, input_name: &str, input: &[u32]) {
     macro_rules! each_maps {
         ($($mapvar:ident, $name:expr, $init:expr),*) => {
@@ -57,6 +62,56 @@ fn benchmark_input(criterion: &mut Criterion, input_name: &str, input: &[u32]) {
                         })
                     });
                 )*
+                drop(bench);
+                let mut bench = criterion.benchmark_group(&format!("clone-{}", input_name));
+                $(
+                    bench.bench_function($name, |bench| {
+                        bench.iter(|| black_box($mapvar.clone()))
+                    });
+                )*
+            }
+            {
+                let mut sorted = input.to_vec();
+                sorted.sort_unstable();
+                sorted.dedup();
+                for (group, keys) in [("collect-sorted", &sorted[..]), ("collect-unsorted", input)] {
+                    let mut bench = criterion.benchmark_group(&format!("{}-{}", group, input_name));
+                    $(
+                        let proto = $init;
+                        bench.bench_function($name, |bench| {
+                            bench.iter(|| black_box(collect_like(&proto, black_box(keys))))
+                        });
+                    )*
+                }
+            }
+            {
+                // The keys of the `left` are all less than the keys of the `right`
+                // for the disjoint case, and interleaved for the overlapping case.
+                for (group, disjoint) in [("append-disjoint", true), ("append-overlap", false)] {
+                    let mut bench = criterion.benchmark_group(&format!("{}-{}", group, input_name));
+                    $(
+                        let mut left = $init;
+                        let mut right = $init;
+                        for &n in input {
+                            let (l, r) = match disjoint {
+                                true => (n >> 1, n >> 1 | 1 << 31),
+                                false => (n & !1, n | 1),
+                            };
+                            left.insert(l, n);
+                            right.insert(r, n);
+                        }
+                        bench.bench_function($name, |bench| {
+                            bench.iter_batched(
+                                || (left.clone(), right.clone()),
+                                |(mut left, mut right)| {
+                                    left.append(&mut right);
+                                    left
+                                },
+                                BatchSize::LargeInput,
+                            )
+                        });
+                    )*
+                }
             }
         }
     }
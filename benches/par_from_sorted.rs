@@ -0,0 +1,26 @@
+//! Only built with the `"rayon"` feature, like `cargo bench --features rayon`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+fn criterion_benchmark(criterion: &mut Criterion) {
+    let entries: Vec<(u64, u64)> = (0..4_000_000).map(|i| (i * 2, i)).collect();
+
+    let mut bench = criterion.benchmark_group("from-sorted");
+    bench.sample_size(10);
+    bench.bench_function("sequential", |bench| {
+        bench.iter(|| {
+            let map: bpt::BTreeMap<u64, u64, 31> = black_box(&entries).iter().copied().collect();
+            black_box(map)
+        })
+    });
+    bench.bench_function("parallel", |bench| {
+        bench.iter(|| {
+            black_box(bpt::BTreeMap::<u64, u64, 31>::par_from_sorted(black_box(
+                &entries,
+            )))
+        })
+    });
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -1,8 +1,10 @@
-use std::cell::RefCell;
-use std::rc::{Rc, Weak};
+use core::cell::RefCell;
 
-pub use std::cell::{Ref, RefMut};
-pub use std::{assert as assume, unreachable};
+use crate::rc::{Rc, Weak};
+use crate::AllocError;
+
+pub use core::cell::{Ref, RefMut};
+pub use core::{assert as assume, unreachable};
 
 #[derive(Debug)]
 pub(super) struct RcCell<T> {
@@ -14,6 +16,12 @@ pub(super) struct WeakCell<T> {
     inner: Weak<RefCell<T>>,
 }
 
+/// Borrowed handle to the content of the `RcCell`.
+#[derive(Debug)]
+pub(super) struct CellRef<'a, T> {
+    inner: &'a RefCell<T>,
+}
+
 impl<T> RcCell<T> {
     pub fn new(value: T) -> Self {
         Self {
@@ -21,6 +29,12 @@ impl<T> RcCell<T> {
         }
     }
 
+    pub fn try_new(value: T) -> Result<Self, AllocError> {
+        Ok(Self {
+            inner: Rc::try_new(RefCell::new(value))?,
+        })
+    }
+
     // pub fn ptr_eq(&self, rhs: &Self) -> bool {
     //     Rc::ptr_eq(&self.inner, &rhs.inner)
     // }
@@ -29,6 +43,10 @@ impl<T> RcCell<T> {
         self.inner.borrow()
     }
 
+    pub fn cell_ref(&self) -> CellRef<'_, T> {
+        CellRef { inner: &self.inner }
+    }
+
     // pub fn deep_clone(&self) -> Self
     // where
     //     T: Clone,
@@ -38,6 +56,13 @@ impl<T> RcCell<T> {
     //     }
     // }
 
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        match Rc::try_unwrap(self.inner) {
+            Ok(cell) => Ok(cell.into_inner()),
+            Err(inner) => Err(Self { inner }),
+        }
+    }
+
     // Methods below may allow to modify reference counts
     // so they must take `&mut self` though the implementation doesn't requires it.
 
@@ -59,46 +84,94 @@ impl<T> RcCell<T> {
 }
 
 impl<T> WeakCell<T> {
-    // pub fn upgrade(&mut self) -> RcCell<T> {
-    //     RcCell {
-    //         inner: self.inner.upgrade().unwrap(),
-    //     }
-    // }
+    /// Returns a strong handle to the pointee, or `None` if it's already dropped.
+    ///
+    /// It takes `&mut self` as it modifies the reference count, same as the `RcCell::shallow_clone`.
+    pub fn upgrade(&mut self) -> Option<RcCell<T>> {
+        Some(RcCell {
+            inner: self.inner.upgrade()?,
+        })
+    }
+
+    /// Follows the link without touching the reference counts.
+    /// Returns `None` if the pointee is already dropped.
+    ///
+    /// # Safety
+    ///
+    /// The pointee must be kept alive by some `RcCell` for the lifetime `'a`.
+    pub unsafe fn deref<'a>(&self) -> Option<CellRef<'a, T>> {
+        if self.inner.strong_count() == 0 {
+            return None;
+        }
+
+        Some(CellRef {
+            inner: unsafe { &*self.inner.as_ptr() },
+        })
+    }
+}
+
+impl<'a, T> Clone for CellRef<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for CellRef<'a, T> {}
+
+impl<'a, T> CellRef<'a, T> {
+    pub fn get(self) -> Ref<'a, T> {
+        self.inner.borrow()
+    }
+
+    /// # Safety
+    ///
+    /// The caller must have the exclusive access to the tree which owns the cell,
+    /// and mustn't hold any other guard of the same cell while the returned one is alive.
+    pub unsafe fn get_mut(self) -> RefMut<'a, T> {
+        self.inner.borrow_mut()
+    }
+
+    pub fn ptr_eq(self, rhs: Self) -> bool {
+        core::ptr::eq(self.inner, rhs.inner)
+    }
 }
 
 #[derive(Debug)]
 pub struct RefKey<'a, T>(RefMut<'a, T>);
 
-impl<'a, T> std::ops::Deref for RefKey<'a, T> {
+impl<'a, T> core::ops::Deref for RefKey<'a, T> {
     type Target = T;
 
     fn deref(&self) -> &Self::Target {
-        &*self.0
+        &self.0
     }
 }
 
-impl<'a, T: std::fmt::Display> std::fmt::Display for RefKey<'a, T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<'a, T: core::fmt::Display> core::fmt::Display for RefKey<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         self.0.fmt(f)
     }
 }
 
-pub fn map_ref<T, U, F: FnOnce(&T) -> &U>(orig: Ref<'_, T>, f: F) -> Ref<'_, U> {
+pub fn map_ref<T: ?Sized, U: ?Sized, F: FnOnce(&T) -> &U>(orig: Ref<'_, T>, f: F) -> Ref<'_, U> {
     Ref::map(orig, f)
 }
 
-pub fn split_ref<T, U, V, F: FnOnce(&T) -> (&U, &V)>(
+pub fn split_ref<T: ?Sized, U: ?Sized, V: ?Sized, F: FnOnce(&T) -> (&U, &V)>(
     orig: Ref<'_, T>,
     f: F,
 ) -> (Ref<'_, U>, Ref<'_, V>) {
     Ref::map_split(orig, f)
 }
 
-pub fn map_mut<T, U, F: FnOnce(&mut T) -> &mut U>(orig: RefMut<'_, T>, f: F) -> RefMut<'_, U> {
+pub fn map_mut<T: ?Sized, U: ?Sized, F: FnOnce(&mut T) -> &mut U>(
+    orig: RefMut<'_, T>,
+    f: F,
+) -> RefMut<'_, U> {
     RefMut::map(orig, f)
 }
 
-pub fn split_mut<T, U, V, F: FnOnce(&mut T) -> (&mut U, &mut V)>(
+pub fn split_mut<T: ?Sized, U: ?Sized, V: ?Sized, F: FnOnce(&mut T) -> (&mut U, &mut V)>(
     orig: RefMut<'_, T>,
     f: F,
 ) -> (RefMut<'_, U>, RefMut<'_, V>) {
@@ -0,0 +1,343 @@
+use core::borrow::Borrow;
+use core::cmp::Ord;
+use core::ops::Bound;
+
+use crate::checked::{Ref, RefKey, RefMut};
+use crate::leaf::LeafRef;
+use crate::node::{Node, Target};
+use crate::BTreeMap;
+
+/// Cursor pointing at an entry of the map, or the ghost position
+/// between the last and the first entry.
+///
+/// It holds the leaf of the current entry, so moving to the adjacent entry
+/// only follows the leaf links without descending the tree.
+pub struct Cursor<'a, K, V, const CAP: usize> {
+    map: &'a BTreeMap<K, V, CAP>,
+    current: Option<(LeafRef<'a, K, V, CAP>, usize)>,
+}
+
+/// Cursor which can modify the map, created by `BTreeMap::lower_bound_cursor_mut`
+/// or `BTreeMap::upper_bound_cursor_mut`.
+///
+/// The leafs may be split or merged by the modification,
+/// so it holds the rank of the current entry and descends the tree on each access.
+pub struct CursorMut<'a, K, V, const CAP: usize> {
+    map: &'a mut BTreeMap<K, V, CAP>,
+    current: Option<usize>,
+}
+
+impl<'a, K, V, const CAP: usize> Clone for Cursor<'a, K, V, CAP> {
+    fn clone(&self) -> Self {
+        Cursor {
+            map: self.map,
+            current: self.current,
+        }
+    }
+}
+
+impl<'a, K: Ord, V, const CAP: usize> Cursor<'a, K, V, CAP> {
+    pub(crate) fn new(
+        map: &'a BTreeMap<K, V, CAP>,
+        current: Option<(LeafRef<'a, K, V, CAP>, usize)>,
+    ) -> Self {
+        Cursor { map, current }
+    }
+
+    /// Returns the current entry, or `None` at the ghost position.
+    #[inline]
+    pub fn key_value(&self) -> Option<(Ref<'a, K>, Ref<'a, V>)> {
+        let (leaf, idx) = self.current?;
+        Some(leaf.entry(idx))
+    }
+
+    #[inline]
+    pub fn key(&self) -> Option<Ref<'a, K>> {
+        self.key_value().map(|(key, _)| key)
+    }
+
+    #[inline]
+    pub fn value(&self) -> Option<Ref<'a, V>> {
+        self.key_value().map(|(_, value)| value)
+    }
+
+    /// Moves to the next entry, or to the first entry from the ghost position.
+    pub fn move_next(&mut self) {
+        self.current = self.next_position();
+    }
+
+    /// Moves to the previous entry, or to the last entry from the ghost position.
+    pub fn move_prev(&mut self) {
+        self.current = self.prev_position();
+    }
+
+    /// Returns the entry the `move_next` would move to, without moving.
+    pub fn peek_next(&self) -> Option<(Ref<'a, K>, Ref<'a, V>)> {
+        let (leaf, idx) = self.next_position()?;
+        Some(leaf.entry(idx))
+    }
+
+    /// Returns the entry the `move_prev` would move to, without moving.
+    pub fn peek_prev(&self) -> Option<(Ref<'a, K>, Ref<'a, V>)> {
+        let (leaf, idx) = self.prev_position()?;
+        Some(leaf.entry(idx))
+    }
+
+    fn next_position(&self) -> Option<(LeafRef<'a, K, V, CAP>, usize)> {
+        match self.current {
+            Some((leaf, idx)) if idx + 1 < leaf.entries().len() => Some((leaf, idx + 1)),
+            Some((leaf, _)) => Some((leaf.next()?, 0)),
+            None => Some((self.map.root.as_ref()?.head().leaf_ref(), 0)),
+        }
+    }
+
+    fn prev_position(&self) -> Option<(LeafRef<'a, K, V, CAP>, usize)> {
+        let leaf = match self.current {
+            Some((leaf, idx)) if idx > 0 => return Some((leaf, idx - 1)),
+            Some((leaf, _)) => leaf.prev()?,
+            None => self.map.root.as_ref()?.tail().leaf_ref(),
+        };
+        let len = leaf.entries().len();
+        Some((leaf, len - 1))
+    }
+}
+
+impl<'a, K: Ord, V, const CAP: usize> CursorMut<'a, K, V, CAP> {
+    pub(crate) fn new(map: &'a mut BTreeMap<K, V, CAP>, current: Option<usize>) -> Self {
+        CursorMut { map, current }
+    }
+
+    /// Returns the current entry, or `None` at the ghost position.
+    #[inline]
+    pub fn key_value(&self) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        self.map.select(self.current?)
+    }
+
+    #[inline]
+    pub fn key(&self) -> Option<Ref<'_, K>> {
+        self.key_value().map(|(key, _)| key)
+    }
+
+    /// Returns the current entry with the value borrowed mutably.
+    #[inline]
+    pub fn key_value_mut(&mut self) -> Option<(RefKey<'_, K>, RefMut<'_, V>)> {
+        let n = self.current?;
+        Some(self.map.root.as_mut()?.select_mut(n))
+    }
+
+    #[inline]
+    pub fn value_mut(&mut self) -> Option<RefMut<'_, V>> {
+        self.key_value_mut().map(|(_, value)| value)
+    }
+
+    /// Moves to the next entry, or to the first entry from the ghost position.
+    pub fn move_next(&mut self) {
+        self.current = self.next_rank();
+    }
+
+    /// Moves to the previous entry, or to the last entry from the ghost position.
+    pub fn move_prev(&mut self) {
+        self.current = self.prev_rank();
+    }
+
+    /// Returns the entry the `move_next` would move to, without moving.
+    pub fn peek_next(&self) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        self.map.select(self.next_rank()?)
+    }
+
+    /// Returns the entry the `move_prev` would move to, without moving.
+    pub fn peek_prev(&self) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        self.map.select(self.prev_rank()?)
+    }
+
+    /// Inserts the entry right after the current one, or at the front from the ghost position.
+    /// The cursor keeps pointing at the current entry.
+    ///
+    /// The cursor doesn't hold the path to its entry, so it descends the tree once to locate
+    /// the `key` and inserts along that path, which takes `O(log n)` time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `key` is not between the current and the next key.
+    pub fn insert_after(&mut self, key: K, value: V) {
+        let rank = self.current.map_or(0, |n| n + 1);
+        let location = self.map.locate(&key);
+        let fits = !location.is_occupied() && location.rank() == rank;
+        assert!(fits, "key doesn't fit after the cursor");
+
+        location.insert(key, value);
+    }
+
+    /// Inserts the entry right before the current one, or at the back from the ghost position.
+    /// The cursor keeps pointing at the current entry.
+    ///
+    /// Same as the `insert_after`, it takes `O(log n)` time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `key` is not between the previous and the current key.
+    pub fn insert_before(&mut self, key: K, value: V) {
+        let rank = self.current.unwrap_or(self.map.len());
+        let location = self.map.locate(&key);
+        let fits = !location.is_occupied() && location.rank() == rank;
+        assert!(fits, "key doesn't fit before the cursor");
+
+        location.insert(key, value);
+        if let Some(n) = &mut self.current {
+            *n += 1;
+        }
+    }
+
+    /// Removes the current entry and moves to the next one.
+    /// Returns `None` and does nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        let n = self.current?;
+        let (key, value, _) = self.map.remove_target(Target::<K>::Nth(n))?;
+        if n == self.map.len() {
+            self.current = None;
+        }
+        Some((key, value))
+    }
+
+    fn next_rank(&self) -> Option<usize> {
+        let n = self.current.map_or(0, |n| n + 1);
+        (n < self.map.len()).then_some(n)
+    }
+
+    fn prev_rank(&self) -> Option<usize> {
+        match self.current {
+            Some(n) => n.checked_sub(1),
+            None => self.map.len().checked_sub(1),
+        }
+    }
+}
+
+impl<K: Ord, V, const CAP: usize> BTreeMap<K, V, CAP> {
+    /// Returns the cursor pointing at the first entry whose key is greater than or equal to the `query`,
+    /// or the ghost position if there's no such entry.
+    #[inline]
+    pub fn lower_bound_cursor<Q>(&self, query: &Q) -> Cursor<'_, K, V, CAP>
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        Cursor::new(self, self.position_after(Bound::Included(query)))
+    }
+
+    /// Returns the cursor pointing at the last entry whose key is less than or equal to the `query`,
+    /// or the ghost position if there's no such entry.
+    #[inline]
+    pub fn upper_bound_cursor<Q>(&self, query: &Q) -> Cursor<'_, K, V, CAP>
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        Cursor::new(self, self.position_before(Bound::Included(query)))
+    }
+
+    /// Same as the `lower_bound_cursor`, but the cursor can modify the map.
+    pub fn lower_bound_cursor_mut<Q>(&mut self, query: &Q) -> CursorMut<'_, K, V, CAP>
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        let n = self.rank(query);
+        let current = (n < self.len()).then_some(n);
+        CursorMut::new(self, current)
+    }
+
+    /// Same as the `upper_bound_cursor`, but the cursor can modify the map.
+    pub fn upper_bound_cursor_mut<Q>(&mut self, query: &Q) -> CursorMut<'_, K, V, CAP>
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        let n = self.rank(query) + usize::from(self.contains_key(query));
+        CursorMut::new(self, n.checked_sub(1))
+    }
+}
+
+#[test]
+fn cursor_walks_forward_and_backward() {
+    let map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i * 2, i)).collect();
+
+    let mut cursor = map.lower_bound_cursor(&501);
+    assert_eq!(cursor.key_value().map(|(k, _)| *k), Some(502));
+    assert_eq!(cursor.peek_prev().map(|(k, _)| *k), Some(500));
+    for expected in (251..1000).map(|i| i * 2) {
+        assert_eq!(cursor.value().map(|v| *v * 2), Some(expected));
+        cursor.move_next();
+    }
+    assert!(cursor.key().is_none());
+    assert_eq!(cursor.peek_next().map(|(k, _)| *k), Some(0));
+    assert_eq!(cursor.peek_prev().map(|(k, _)| *k), Some(1998));
+
+    let mut cursor = map.upper_bound_cursor(&501);
+    assert_eq!(cursor.key_value().map(|(k, _)| *k), Some(500));
+    for expected in (0..=250).rev().map(|i| i * 2) {
+        assert_eq!(cursor.key_value().map(|(k, _)| *k), Some(expected));
+        cursor.move_prev();
+    }
+    assert!(cursor.key().is_none());
+    cursor.move_prev();
+    assert_eq!(cursor.key_value().map(|(k, _)| *k), Some(1998));
+
+    assert!(map.lower_bound_cursor(&1999).key().is_none());
+    assert!(map.upper_bound_cursor(&0).key().is_some());
+    assert!(BTreeMap::<u32, u32, 5>::new()
+        .lower_bound_cursor(&0)
+        .peek_next()
+        .is_none());
+}
+
+#[test]
+fn cursor_mut_inserts_and_removes_mid_traversal() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..500).map(|i| (i * 4, i)).collect();
+
+    // Fill the gaps after every key and drop every multiple of 8.
+    let mut cursor = map.lower_bound_cursor_mut(&0);
+    while let Some(key) = cursor.key_value().map(|(k, _)| *k) {
+        if key % 8 == 0 {
+            assert_eq!(cursor.remove_current(), Some((key, key / 4)));
+            continue;
+        }
+        cursor.insert_after(key + 1, 0);
+        cursor.insert_before(key - 1, 0);
+        *cursor.value_mut().unwrap() += 1;
+        cursor.move_next();
+        cursor.move_next();
+    }
+    assert!(cursor.peek_prev().is_some());
+
+    map.check_invariants();
+    let expected: Vec<u32> = (0..500)
+        .map(|i| i * 4)
+        .filter(|k| k % 8 != 0)
+        .flat_map(|k| [k - 1, k, k + 1])
+        .collect();
+    assert!(map.keys().zip(&expected).all(|(k, e)| *k == *e));
+    assert_eq!(map.len(), expected.len());
+    assert_eq!(*map.get(&4).unwrap(), 2);
+
+    let mut cursor = map.upper_bound_cursor_mut(&u32::MAX);
+    assert_eq!(cursor.key_value().map(|(k, _)| *k), Some(1997));
+    cursor.move_next();
+    cursor.insert_after(0, 0);
+    cursor.insert_before(2000, 0);
+    assert_eq!(map.first_key_value().map(|(k, _)| *k), Some(0));
+    assert_eq!(map.last_key_value().map(|(k, _)| *k), Some(2000));
+}
+
+#[test]
+#[should_panic(expected = "key doesn't fit after the cursor")]
+fn cursor_mut_panics_on_unordered_insert() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i * 2, i)).collect();
+    map.lower_bound_cursor_mut(&10).insert_after(13, 0);
+}
+
+#[test]
+#[should_panic(expected = "key doesn't fit before the cursor")]
+fn cursor_mut_panics_on_inserting_the_current_key() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i * 2, i)).collect();
+    map.lower_bound_cursor_mut(&10).insert_before(10, 0);
+}
@@ -0,0 +1,285 @@
+use core::cmp::Ord;
+use core::error::Error;
+use core::fmt;
+
+use crate::checked::{self, Ref, RefKey, RefMut};
+use crate::location::Location;
+use crate::BTreeMap;
+
+/// View into a single entry of the map, found by `BTreeMap::entry_at`.
+///
+/// Both variants keep the position found by the descent,
+/// so reading or inserting through them doesn't descend the tree again.
+#[derive(Debug)]
+pub enum Entry<'a, K, V, const CAP: usize> {
+    Occupied(OccupiedEntry<'a, K, V, CAP>),
+    Vacant(VacantEntry<'a, K, V, CAP>),
+}
+
+/// Entry of a key which exists in the map.
+#[derive(Debug)]
+pub struct OccupiedEntry<'a, K, V, const CAP: usize> {
+    location: Location<'a, K, V, CAP>,
+}
+
+/// Entry of a key which doesn't exist in the map.
+#[derive(Debug)]
+pub struct VacantEntry<'a, K, V, const CAP: usize> {
+    key: K,
+    location: Location<'a, K, V, CAP>,
+}
+
+/// Result of the `BTreeMap::entry_or_vacant_mut`, which is either the existing entry
+/// or the vacant entry to insert without descending again.
+pub type EntryMutResult<'a, K, V, const CAP: usize> =
+    Result<(RefKey<'a, K>, RefMut<'a, V>), VacantEntry<'a, K, V, CAP>>;
+
+/// Error returned by `BTreeMap::try_insert` if the key already exists.
+///
+/// It holds the existing entry and the value which is not inserted.
+#[derive(Debug)]
+pub struct OccupiedError<'a, K, V, const CAP: usize> {
+    pub entry: OccupiedEntry<'a, K, V, CAP>,
+    pub value: V,
+}
+
+impl<'a, K: Ord + fmt::Debug, V: fmt::Debug, const CAP: usize> fmt::Display
+    for OccupiedError<'a, K, V, CAP>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to insert {:?}, key {:?} already exists with value {:?}",
+            self.value,
+            self.entry.key(),
+            self.entry.get(),
+        )
+    }
+}
+
+impl<'a, K: Ord + fmt::Debug, V: fmt::Debug, const CAP: usize> Error
+    for OccupiedError<'a, K, V, CAP>
+{
+}
+
+impl<'a, K: Ord, V, const CAP: usize> Entry<'a, K, V, CAP> {
+    pub(crate) fn new(map: &'a mut BTreeMap<K, V, CAP>, key: K) -> Self {
+        let location = map.locate(&key);
+        match location.is_occupied() {
+            true => Entry::Occupied(OccupiedEntry { location }),
+            false => Entry::Vacant(VacantEntry { key, location }),
+        }
+    }
+
+    /// Inserts the `default` if vacant, and returns the value.
+    #[inline]
+    pub fn or_insert(self, default: V) -> RefMut<'a, V> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the value returned by the `default` if vacant, and returns the value.
+    #[inline]
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> RefMut<'a, V> {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    /// Calls the `f` with the value if occupied.
+    #[inline]
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+            f(&mut entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K: Ord, V: Default, const CAP: usize> Entry<'a, K, V, CAP> {
+    /// Inserts the default value if vacant, and returns the value.
+    #[inline]
+    pub fn or_default(self) -> RefMut<'a, V> {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<'a, K: Ord, V, const CAP: usize> OccupiedEntry<'a, K, V, CAP> {
+    pub(crate) fn new(location: Location<'a, K, V, CAP>) -> Self {
+        OccupiedEntry { location }
+    }
+
+    #[inline]
+    pub fn key(&self) -> Ref<'_, K> {
+        self.entry().0
+    }
+
+    #[inline]
+    pub fn get(&self) -> Ref<'_, V> {
+        self.entry().1
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> RefMut<'_, V> {
+        match self.location.get_mut() {
+            Some(value) => value,
+            None => checked::unreachable!("entry should be occupied"),
+        }
+    }
+
+    /// Converts the entry into the value borrowed for the lifetime of the map borrow.
+    #[inline]
+    pub fn into_mut(self) -> RefMut<'a, V> {
+        match self.location.into_mut() {
+            Some(value) => value,
+            None => checked::unreachable!("entry should be occupied"),
+        }
+    }
+
+    /// Replaces the value and returns the old one.
+    #[inline]
+    #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+    pub fn insert(&mut self, value: V) -> V {
+        core::mem::replace(&mut self.get_mut(), value)
+    }
+
+    /// Removes the entry from the map and returns it.
+    #[inline]
+    pub fn remove_entry(self) -> (K, V) {
+        match self.location.remove() {
+            Some(entry) => entry,
+            None => checked::unreachable!("entry should be occupied"),
+        }
+    }
+
+    /// Removes the entry from the map and returns its value.
+    #[inline]
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    fn entry(&self) -> (Ref<'_, K>, Ref<'_, V>) {
+        match self.location.get() {
+            Some(entry) => entry,
+            None => checked::unreachable!("entry should be occupied"),
+        }
+    }
+}
+
+impl<'a, K: Ord, V, const CAP: usize> VacantEntry<'a, K, V, CAP> {
+    pub(crate) fn new(key: K, location: Location<'a, K, V, CAP>) -> Self {
+        VacantEntry { key, location }
+    }
+
+    #[inline]
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    #[inline]
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Inserts the value and returns it.
+    #[inline]
+    pub fn insert(self, value: V) -> RefMut<'a, V> {
+        self.location.insert_vacant(self.key, value)
+    }
+}
+
+#[test]
+fn entry_counts_words() {
+    let text = "the quick brown fox jumps over the lazy dog the end";
+    let mut map = BTreeMap::<&str, usize, 5>::new();
+    let mut expected = std::collections::BTreeMap::new();
+    for word in text.split(' ') {
+        map.entry_at(word).and_modify(|n| *n += 1).or_insert(1);
+        *expected.entry(word).or_insert(0) += 1;
+    }
+
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq(expected.iter().map(|(k, v)| (*k, *v))));
+}
+
+#[test]
+fn entry_inserts_default_across_splits() {
+    let mut map = BTreeMap::<u32, Vec<u32>, 5>::new();
+    let nums: Vec<u32> = std::iter::repeat_with(|| rand::random::<u32>() % 512)
+        .take(4096)
+        .collect();
+    for (i, &n) in nums.iter().enumerate() {
+        map.entry_at(n).or_default().push(i as u32);
+    }
+
+    for (key, value) in map.iter() {
+        let expected: Vec<u32> = (0..nums.len() as u32)
+            .filter(|&i| nums[i as usize] == *key)
+            .collect();
+        assert_eq!(*value, expected);
+    }
+
+    match map.entry_at(nums[0]) {
+        Entry::Occupied(mut entry) => {
+            assert_eq!(*entry.key(), nums[0]);
+            let old = entry.insert(vec![]);
+            assert_eq!(old[0], 0);
+        }
+        Entry::Vacant(_) => panic!("key should exist"),
+    }
+    assert!(map.get(&nums[0]).unwrap().is_empty());
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut, dropping_references))]
+fn try_insert_rejects_existing_key() {
+    let mut map = BTreeMap::<u32, String, 5>::new();
+    for i in 0..100 {
+        map.insert(i * 2, i.to_string());
+    }
+
+    let mut value = map.try_insert(41, "new".into()).unwrap();
+    value.push('!');
+    drop(value);
+    assert_eq!(map.get(&41).unwrap().as_str(), "new!");
+
+    let err = map.try_insert(40, "rejected".into()).unwrap_err();
+    assert_eq!(*err.entry.key(), 40);
+    assert_eq!(err.entry.get().as_str(), "20");
+    assert_eq!(err.value, "rejected");
+    assert_eq!(
+        err.to_string(),
+        r#"failed to insert "rejected", key 40 already exists with value "20""#
+    );
+    assert_eq!(map.get(&40).unwrap().as_str(), "20");
+    assert_eq!(map.len(), 101);
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn entry_or_vacant_mut_upserts_with_single_descent() {
+    use crate::test_util::{comparisons, Counted};
+
+    let mut map = BTreeMap::<Counted, u32, 5>::new();
+
+    for i in (0..2000).map(|i| i * 7919 % 1000) {
+        match map.entry_or_vacant_mut(Counted(i)) {
+            Ok((key, mut value)) => {
+                assert_eq!(key.0, i);
+                *value += 1;
+            }
+            Err(vacant) => {
+                comparisons();
+                vacant.insert(1);
+                // Only the sanity check against the neighbors of the slot.
+                assert!(comparisons() <= 2, "insert shouldn't descend again");
+            }
+        }
+    }
+
+    assert_eq!(map.len(), 1000);
+    assert!(map.values().all(|v| *v == 2));
+}
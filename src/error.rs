@@ -0,0 +1,44 @@
+use core::error::Error;
+use core::fmt;
+
+/// Upper limit of the `CAP * size_of::<(K, V)>()`.
+///
+/// Splitting a node moves an array of `CAP` entries on the stack,
+/// so a larger node may overflow the stack of a thread before it reaches the heap.
+pub const MAX_NODE_BYTES: usize = 256 * 1024;
+
+/// The `CAP` parameter of the `BTreeMap` violates its constraints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapacityError {
+    /// `CAP` is less than 4.
+    TooSmall,
+    /// `CAP` entries take more than `MAX_NODE_BYTES` bytes.
+    TooLarge,
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CapacityError::TooSmall => write!(f, "Node capacity must be at least 4"),
+            CapacityError::TooLarge => write!(
+                f,
+                "Node capacity times the entry size must not exceed {} bytes",
+                MAX_NODE_BYTES
+            ),
+        }
+    }
+}
+
+impl Error for CapacityError {}
+
+/// The allocator failed to provide the memory of a new node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Failed to allocate a node")
+    }
+}
+
+impl Error for AllocError {}
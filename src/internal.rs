@@ -1,17 +1,21 @@
-use std::borrow::Borrow;
-use std::cmp::{Ord, Ordering};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ord;
 
 use arrayvec::ArrayVec;
 
 use crate::checked::{self, Ref, RefKey, RefMut};
-use crate::insert_or_split;
 use crate::leaf::Leaf;
-use crate::node::Node;
+use crate::node::{Node, Route, Spare, Target};
+use crate::{distribute, insert_or_split, redistribute};
 
 #[derive(Debug)]
 pub(crate) struct Internal<K, V, const CAP: usize> {
     head: Leaf<K, V, CAP>,
     tail: Leaf<K, V, CAP>,
+    /// Number of the entries in this subtree.
+    len: usize,
     children: Children<K, V, CAP>,
 }
 
@@ -26,6 +30,7 @@ impl<K: Ord, V, const CAP: usize> Internal<K, V, CAP> {
         Self {
             head: leaf.shallow_clone(),
             tail: leaf.shallow_clone(),
+            len: leaf.len(),
             children: Children::Leaf([leaf].into_iter().collect()),
         }
     }
@@ -34,10 +39,83 @@ impl<K: Ord, V, const CAP: usize> Internal<K, V, CAP> {
         Self {
             head: left.head.shallow_clone(),
             tail: right.tail.shallow_clone(),
+            len: left.len + right.len,
             children: Children::Internal([left, right].into_iter().collect()),
         }
     }
 
+    /// Builds the tree over the leafs, which must be sorted, linked
+    /// and not underfull unless there's only one.
+    pub fn build(leaves: Vec<Leaf<K, V, CAP>>) -> Box<Self> {
+        let nodes = distribute(leaves)
+            .into_iter()
+            .map(Self::from_leaves)
+            .collect();
+        Self::build_spine(nodes)
+    }
+
+    /// Builds the bottom node over the leafs, which must be sorted and linked.
+    pub fn from_leaves(leaves: ArrayVec<Leaf<K, V, CAP>, CAP>) -> Box<Self> {
+        Self::with_children(Children::Leaf(leaves))
+    }
+
+    /// Builds the levels above the nodes of the same height,
+    /// which must be sorted and have their leafs linked.
+    pub fn build_spine(mut nodes: Vec<Box<Self>>) -> Box<Self> {
+        while nodes.len() > 1 {
+            nodes = distribute(nodes)
+                .into_iter()
+                .map(|children| Self::with_children(Children::Internal(children)))
+                .collect();
+        }
+
+        nodes
+            .pop()
+            .unwrap_or_else(|| checked::unreachable!("tree should have at least one leaf"))
+    }
+
+    /// Links the last leaf of this subtree to the first leaf of the `next`.
+    pub fn link_next(&mut self, next: &mut Self) {
+        self.tail.link_next(&mut next.head);
+    }
+
+    fn with_children(mut children: Children<K, V, CAP>) -> Box<Self> {
+        let (head, tail) = match &mut children {
+            Children::Internal(children) => head_tail(children),
+            Children::Leaf(children) => head_tail(children),
+        };
+
+        Box::new(Self {
+            head,
+            tail,
+            len: children.len(),
+            children,
+        })
+    }
+
+    /// Recomputes the head, the tail and the length from the children.
+    fn refresh(&mut self) {
+        let (head, tail) = match &mut self.children {
+            Children::Internal(children) => head_tail(children),
+            Children::Leaf(children) => head_tail(children),
+        };
+        self.head = head;
+        self.tail = tail;
+        self.len = self.children.len();
+    }
+
+    /// Dismantles the tree, collecting its leafs in order.
+    pub fn into_leaves(self, out: &mut Vec<Leaf<K, V, CAP>>) {
+        match self.children {
+            Children::Internal(children) => {
+                for child in children {
+                    child.into_leaves(out);
+                }
+            }
+            Children::Leaf(children) => out.extend(children),
+        }
+    }
+
     pub fn check_lower<Q: Ord>(&self, query: &Q) -> Option<()>
     where
         K: Borrow<Q>,
@@ -56,6 +134,183 @@ impl<K: Ord, V, const CAP: usize> Internal<K, V, CAP> {
         }
     }
 
+    /// Replaces the root with its only child until it has more than one or holds the leafs.
+    fn shrink(root: &mut Box<Self>) {
+        while let Some(child) = root.pop_depth() {
+            *root = child;
+        }
+    }
+
+    /// Splits the tree at the `n`-th entry, which must be within `1..root.len`,
+    /// leaving the smaller entries in the `root` and returning the root of the rest.
+    ///
+    /// Only the nodes along the cut are split and rebalanced.
+    pub fn split_nth(root: &mut Box<Self>, n: usize) -> Box<Self> {
+        let mut right = root.split_off_nth(n);
+        root.fix_right_border();
+        right.fix_left_border();
+        Self::shrink(root);
+        Self::shrink(&mut right);
+        right
+    }
+
+    /// Concatenates two trees where every key of the `left` is less than the keys of the `right`.
+    ///
+    /// The lower tree is grafted as is onto the border of the higher one,
+    /// and only the nodes along that border are rebalanced.
+    pub fn join(mut left: Box<Self>, mut right: Box<Self>) -> Box<Self> {
+        left.link_next(&mut right);
+
+        let (left_height, right_height) = (left.height(), right.height());
+        let mut root = match left_height.cmp(&right_height) {
+            core::cmp::Ordering::Greater => {
+                let new_node = left.push_back(right, left_height - right_height - 1);
+                let mut root = match new_node {
+                    Some(node) => Box::new(Self::wrap(left, node)),
+                    None => left,
+                };
+                root.fix_right_border();
+                root
+            }
+            core::cmp::Ordering::Less => {
+                let new_node = right.push_front(left, right_height - left_height - 1);
+                let mut root = match new_node {
+                    Some(node) => Box::new(Self::wrap(right, node)),
+                    None => right,
+                };
+                root.fix_left_border();
+                root
+            }
+            core::cmp::Ordering::Equal => {
+                let mut root = match left.redistribute(&mut right) {
+                    true => left,
+                    false => Box::new(Self::wrap(left, right)),
+                };
+                // A single leaf tree may have an underfull leaf on either side of the junction.
+                root.fix_left_border();
+                root.fix_right_border();
+                root
+            }
+        };
+
+        Self::shrink(&mut root);
+        root
+    }
+
+    /// Adds the `tree` as the last subtree `depth` levels below the children of this node,
+    /// splitting the nodes on the way if they overflow.
+    /// Returns the new sibling if this node is split.
+    fn push_back(&mut self, tree: Box<Self>, depth: usize) -> Option<Box<Self>> {
+        let children = match &mut self.children {
+            Children::Internal(children) => children,
+            Children::Leaf(_) => checked::unreachable!("tree should be lower than the node"),
+        };
+
+        let new_node = match depth.checked_sub(1) {
+            None => Some(tree),
+            Some(depth) => children.last_mut().unwrap().push_back(tree, depth),
+        };
+        let idx = children.len();
+        let split = new_node.and_then(|node| insert_or_split(children, idx, node));
+
+        self.refresh();
+        split.map(|children| Self::with_children(Children::Internal(children)))
+    }
+
+    /// Same as the `push_back`, but adds the `tree` as the first subtree.
+    fn push_front(&mut self, tree: Box<Self>, depth: usize) -> Option<Box<Self>> {
+        let children = match &mut self.children {
+            Children::Internal(children) => children,
+            Children::Leaf(_) => checked::unreachable!("tree should be lower than the node"),
+        };
+
+        // The tree goes first, while the new sibling of the first child goes right after it.
+        let (idx, new_node) = match depth.checked_sub(1) {
+            None => (0, Some(tree)),
+            Some(depth) => (1, children[0].push_front(tree, depth)),
+        };
+        let split = new_node.and_then(|node| insert_or_split(children, idx, node));
+
+        self.refresh();
+        split.map(|children| Self::with_children(Children::Internal(children)))
+    }
+
+    /// Rebalances the nodes along the leftmost path, which may be underfull
+    /// as long as every other node in the subtree is not.
+    ///
+    /// Each node on the path is refilled from its next sibling before descending into it,
+    /// so it still has a sibling to merge with after its own child is merged.
+    fn fix_left_border(&mut self) {
+        match &mut self.children {
+            Children::Internal(children) => {
+                balance_first(children);
+                children[0].fix_left_border();
+                balance_first(children);
+            }
+            Children::Leaf(children) => balance_first(children),
+        }
+        self.refresh();
+    }
+
+    /// Same as the `fix_left_border`, but along the rightmost path.
+    fn fix_right_border(&mut self) {
+        match &mut self.children {
+            Children::Internal(children) => {
+                balance_last(children);
+                children.last_mut().unwrap().fix_right_border();
+                balance_last(children);
+            }
+            Children::Leaf(children) => balance_last(children),
+        }
+        self.refresh();
+    }
+
+    pub fn height(&self) -> usize {
+        match &self.children {
+            Children::Internal(children) => 1 + children[0].height(),
+            Children::Leaf(_) => 1,
+        }
+    }
+
+    /// Returns the number of internal nodes and leafs in the subtree.
+    pub fn node_counts(&self) -> (usize, usize) {
+        match &self.children {
+            Children::Internal(children) => children.iter().fold((1, 0), |(i, l), child| {
+                let (ci, cl) = child.node_counts();
+                (i + ci, l + cl)
+            }),
+            Children::Leaf(children) => (1, children.len()),
+        }
+    }
+
+    /// Returns the leaf where the `query` is or would be inserted.
+    pub fn leaf_for<Q: Ord>(&self, query: &Q) -> &Leaf<K, V, CAP>
+    where
+        K: Borrow<Q>,
+    {
+        match &self.children {
+            Children::Internal(children) => children[find_idx(children, query)].leaf_for(query),
+            Children::Leaf(children) => &children[find_idx(children, query)],
+        }
+    }
+
+    /// Returns how many levels from the leaf up would split by inserting the `key`,
+    /// and the number of the levels including the leaf.
+    pub fn split_depth(&self, key: &K) -> (usize, usize) {
+        let ((splits, levels), full) = match &self.children {
+            Children::Internal(children) => (
+                children[find_idx(children, key)].split_depth(key),
+                children.is_full(),
+            ),
+            Children::Leaf(children) => {
+                let leaf = &children[find_idx(children, key)];
+                let split = leaf.len() == CAP && leaf.get(key).is_none();
+                ((usize::from(split), 1), children.is_full())
+            }
+        };
+        (splits + usize::from(splits == levels && full), levels + 1)
+    }
+
     fn child_idx<Q: Ord>(&self, query: &Q) -> Option<usize>
     where
         K: Borrow<Q>,
@@ -76,6 +331,79 @@ impl<K: Ord, V, const CAP: usize> Internal<K, V, CAP> {
     }
 }
 
+impl<K, V, const CAP: usize> Internal<K, V, CAP> {
+    /// Same as the `Node::head`, but without requiring `K: Ord`.
+    pub fn first_leaf(&self) -> &Leaf<K, V, CAP> {
+        &self.head
+    }
+}
+
+impl<K: Ord, V, const CAP: usize> Children<K, V, CAP> {
+    /// Sums the number of the entries of the children.
+    fn len(&self) -> usize {
+        match self {
+            Children::Internal(children) => children.iter().map(Node::len).sum(),
+            Children::Leaf(children) => children.iter().map(Node::len).sum(),
+        }
+    }
+}
+
+impl<K: Ord, V, const CAP: usize> Internal<K, V, CAP> {
+    /// Checks the structure of the subtree and collects its leafs in order.
+    /// Returns the height of the subtree.
+    pub fn check_invariants<'a>(
+        &'a self,
+        is_root: bool,
+        leaves: &mut Vec<crate::leaf::LeafRef<'a, K, V, CAP>>,
+    ) -> usize {
+        let b = CAP.div_ceil(2);
+        let first = leaves.len();
+
+        let (count, len, height) = match &self.children {
+            Children::Internal(children) => {
+                let mut heights = children
+                    .iter()
+                    .map(|child| child.check_invariants(false, leaves));
+                let height = heights.next().unwrap();
+                assert!(
+                    heights.all(|h| h == height),
+                    "leafs should be in the same depth"
+                );
+                assert!(
+                    !is_root || children.len() >= 2,
+                    "root should have at least 2 internal children"
+                );
+                let len: usize = children.iter().map(|child| child.len).sum();
+                (children.len(), len, height + 1)
+            }
+            Children::Leaf(children) => {
+                leaves.extend(children.iter().map(Leaf::leaf_ref));
+                (
+                    children.len(),
+                    children.iter().map(Leaf::len).sum::<usize>(),
+                    1,
+                )
+            }
+        };
+
+        assert!(
+            count <= CAP && (is_root || count >= b),
+            "node has {} children",
+            count
+        );
+        assert_eq!(self.len, len, "stored length of the subtree");
+        assert!(
+            self.head.leaf_ref().ptr_eq(leaves[first]),
+            "head should be the first leaf of the subtree"
+        );
+        assert!(
+            self.tail.leaf_ref().ptr_eq(leaves[leaves.len() - 1]),
+            "tail should be the last leaf of the subtree"
+        );
+        height
+    }
+}
+
 impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
     fn head(&self) -> &Leaf<K, V, CAP> {
         &self.head
@@ -93,9 +421,74 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
         &mut self.tail
     }
 
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn rank<Q: Ord>(&self, query: &Q) -> usize
+    where
+        K: Borrow<Q>,
+    {
+        fn child_rank<N: Node<K, V, CAP>, Q: Ord, K: Ord + Borrow<Q>, V, const CAP: usize>(
+            children: &[N],
+            query: &Q,
+        ) -> usize {
+            let idx = find_idx(children, query);
+            children[..idx].iter().map(N::len).sum::<usize>() + children[idx].rank(query)
+        }
+
+        match &self.children {
+            Children::Internal(children) => child_rank(children, query),
+            Children::Leaf(children) => child_rank(children, query),
+        }
+    }
+
+    fn partition_point<F: FnMut(&K) -> bool>(&self, pred: &mut F) -> usize {
+        fn child_point<N: Node<K, V, CAP>, K, V, const CAP: usize, F: FnMut(&K) -> bool>(
+            children: &[N],
+            pred: &mut F,
+        ) -> usize {
+            // The child right before the first one whose head doesn't satisfy the `pred`.
+            #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+            let idx = children[1..].partition_point(|node| pred(&node.head().first()));
+            children[..idx].iter().map(N::len).sum::<usize>() + children[idx].partition_point(pred)
+        }
+
+        match &self.children {
+            Children::Internal(children) => child_point(children, pred),
+            Children::Leaf(children) => child_point(children, pred),
+        }
+    }
+
+    fn select(&self, n: usize) -> (Ref<'_, K>, Ref<'_, V>) {
+        match &self.children {
+            Children::Internal(children) => {
+                let (idx, n) = nth_child(children, n);
+                children[idx].select(n)
+            }
+            Children::Leaf(children) => {
+                let (idx, n) = nth_child(children, n);
+                children[idx].select(n)
+            }
+        }
+    }
+
+    fn select_mut(&mut self, n: usize) -> (RefKey<'_, K>, RefMut<'_, V>) {
+        match &mut self.children {
+            Children::Internal(children) => {
+                let (idx, n) = nth_child(children, n);
+                children[idx].select_mut(n)
+            }
+            Children::Leaf(children) => {
+                let (idx, n) = nth_child(children, n);
+                children[idx].select_mut(n)
+            }
+        }
+    }
+
     fn get<Q: Ord>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
     where
-        K: std::borrow::Borrow<Q>,
+        K: core::borrow::Borrow<Q>,
     {
         let idx = self.child_idx(query)?;
         match &self.children {
@@ -106,7 +499,7 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
 
     fn get_mut<Q: Ord>(&mut self, query: &Q) -> Option<(RefKey<'_, K>, RefMut<'_, V>)>
     where
-        K: std::borrow::Borrow<Q>,
+        K: core::borrow::Borrow<Q>,
     {
         let idx = self.child_idx(query)?;
         match &mut self.children {
@@ -115,16 +508,87 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
         }
     }
 
-    fn insert(&mut self, new_entry: (K, V)) -> (Option<(K, V)>, Option<Self>) {
+    fn get_at(&self, path: &[usize], idx: usize) -> (Ref<'_, K>, Ref<'_, V>) {
+        match &self.children {
+            Children::Internal(children) => children[path[0]].get_at(&path[1..], idx),
+            Children::Leaf(children) => children[path[0]].get_at(&path[1..], idx),
+        }
+    }
+
+    fn leaf_at_mut(&mut self, path: &[usize]) -> &mut Leaf<K, V, CAP> {
+        match &mut self.children {
+            Children::Internal(children) => children[path[0]].leaf_at_mut(&path[1..]),
+            Children::Leaf(children) => children[path[0]].leaf_at_mut(&path[1..]),
+        }
+    }
+
+    fn locate<Q: Ord>(&self, query: &Q, path: &mut Vec<usize>) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+    {
+        match &self.children {
+            Children::Internal(children) => {
+                let idx = find_idx(children, query);
+                path.push(idx);
+                children[idx].locate(query, path)
+            }
+            Children::Leaf(children) => {
+                let idx = find_idx(children, query);
+                path.push(idx);
+                children[idx].locate(query, path)
+            }
+        }
+    }
+
+    fn locate_nth(&self, n: usize, path: &mut Vec<usize>) -> usize {
+        match &self.children {
+            Children::Internal(children) => {
+                let (idx, n) = nth_child(children, n);
+                path.push(idx);
+                children[idx].locate_nth(n, path)
+            }
+            Children::Leaf(children) => {
+                let (idx, n) = nth_child(children, n);
+                path.push(idx);
+                children[idx].locate_nth(n, path)
+            }
+        }
+    }
+
+    fn rank_at(&self, path: &[usize], idx: usize) -> usize {
+        fn child_rank<N: Node<K, V, CAP>, K, V, const CAP: usize>(
+            children: &[N],
+            path: &[usize],
+            idx: usize,
+        ) -> usize {
+            children[..path[0]].iter().map(N::len).sum::<usize>()
+                + children[path[0]].rank_at(&path[1..], idx)
+        }
+
+        match &self.children {
+            Children::Internal(children) => child_rank(children, path, idx),
+            Children::Leaf(children) => child_rank(children, path, idx),
+        }
+    }
+
+    fn insert(
+        &mut self,
+        new_entry: (K, V),
+        route: Route<'_>,
+        spare: &mut Spare<K, V, CAP>,
+    ) -> (Option<(K, V)>, Option<Self>) {
+        #[allow(clippy::type_complexity)]
         fn insert_entry<N: Node<K, V, CAP>, K: Ord, V, const CAP: usize>(
             nodes: &mut ArrayVec<N, CAP>,
             entry: (K, V),
+            route: Route<'_>,
+            spare: &mut Spare<K, V, CAP>,
             prev_out: &mut Option<(K, V)>,
         ) -> Option<(ArrayVec<N, CAP>, Leaf<K, V, CAP>, Leaf<K, V, CAP>)> {
-            let idx = find_idx(&nodes, &entry.0);
+            let (idx, route) = route.descend(|| find_idx(nodes, &entry.0));
             let child = &mut nodes[idx];
 
-            let (prev, new_node) = child.insert(entry);
+            let (prev, new_node) = child.insert(entry, route, spare);
             *prev_out = prev;
 
             let mut children = insert_or_split(nodes, idx + 1, new_node?)?;
@@ -144,7 +608,7 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
 
         let new_node = match &mut self.children {
             Children::Internal(children) => {
-                let res = insert_entry(children, new_entry, &mut prev);
+                let res = insert_entry(children, new_entry, route, spare, &mut prev);
                 if prev.is_none() {
                     self.tail = children.last_mut().unwrap().tail_mut().shallow_clone();
                 }
@@ -153,13 +617,15 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
                         children: Children::Internal(children),
                         head,
                         tail,
+                        len: 0,
                     }
                 } else {
+                    self.len += usize::from(prev.is_none());
                     return (prev, None);
                 }
             }
             Children::Leaf(children) => {
-                let res = insert_entry(children, new_entry, &mut prev);
+                let res = insert_entry(children, new_entry, route, spare, &mut prev);
                 if prev.is_none() {
                     self.tail = children.last_mut().unwrap().tail_mut().shallow_clone();
                 }
@@ -168,27 +634,32 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
                         children: Children::Leaf(children),
                         head,
                         tail,
+                        len: 0,
                     }
                 } else {
+                    self.len += usize::from(prev.is_none());
                     return (prev, None);
                 }
             }
         };
 
-        (prev, Some(Box::new(new_node)))
+        let mut new_node = spare.internal(new_node);
+        self.len = self.children.len();
+        new_node.len = new_node.children.len();
+        (prev, Some(new_node))
     }
 
-    fn remove<Q: Ord>(&mut self, query: &Q) -> Option<((K, V), bool)>
+    fn remove<Q: Ord>(&mut self, target: Target<'_, Q>) -> Option<((K, V), bool)>
     where
         K: Borrow<Q>,
     {
         fn remove_entry<N: Node<K, V, CAP>, Q: Ord, K: Ord + Borrow<Q>, V, const CAP: usize>(
             children: &mut ArrayVec<N, CAP>,
             idx: usize,
-            query: &Q,
+            target: Target<'_, Q>,
             tail: &mut Leaf<K, V, CAP>,
         ) -> Option<((K, V), bool)> {
-            let (entry, need_merge) = children[idx].remove(query)?;
+            let (entry, need_merge) = children[idx].remove(target)?;
 
             if !need_merge {
                 *tail = children.last_mut().unwrap().tail_mut().shallow_clone();
@@ -210,17 +681,35 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
                 children.remove(left_idx + 1);
             }
 
-            let b = CAP / 2 + 1;
+            let b = CAP.div_ceil(2);
             *tail = children.last_mut().unwrap().tail_mut().shallow_clone();
             Some((entry, children.len() < b))
         }
 
-        let idx = self.child_idx(query)?;
+        let (idx, target) = match target {
+            Target::Key(query) => (self.child_idx(query)?, target),
+            Target::First => (0, target),
+            Target::Last => match &self.children {
+                Children::Internal(children) => (children.len() - 1, target),
+                Children::Leaf(children) => (children.len() - 1, target),
+            },
+            Target::Nth(n) => {
+                let (idx, n) = match &self.children {
+                    Children::Internal(children) => nth_child(children, n),
+                    Children::Leaf(children) => nth_child(children, n),
+                };
+                (idx, Target::Nth(n))
+            }
+        };
 
-        match &mut self.children {
-            Children::Internal(children) => remove_entry(children, idx, query, &mut self.tail),
-            Children::Leaf(children) => remove_entry(children, idx, query, &mut self.tail),
+        let removed = match &mut self.children {
+            Children::Internal(children) => remove_entry(children, idx, target, &mut self.tail),
+            Children::Leaf(children) => remove_entry(children, idx, target, &mut self.tail),
+        };
+        if removed.is_some() {
+            self.len -= 1;
         }
+        removed
     }
 
     fn balance_or_drain(&mut self, next_node: &mut Self, lacking_next: bool) -> bool {
@@ -229,7 +718,7 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
             next: &mut ArrayVec<N, CAP>,
             lacking_next: bool,
         ) -> bool {
-            let b = CAP / 2 + 1;
+            let b = CAP.div_ceil(2);
 
             if lacking_next && this.len() > b {
                 next.insert(0, this.pop().unwrap());
@@ -270,6 +759,8 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
         };
 
         self.tail = this_tail;
+        self.len = self.children.len();
+        next_node.len = next_node.children.len();
 
         match next_head {
             Some(head) => {
@@ -279,21 +770,145 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Box<Internal<K, V, CAP>> {
             None => true,
         }
     }
+
+    fn occupancy(&self) -> usize {
+        match &self.children {
+            Children::Internal(children) => children.len(),
+            Children::Leaf(children) => children.len(),
+        }
+    }
+
+    fn redistribute(&mut self, next: &mut Self) -> bool {
+        let drained = match (&mut self.children, &mut next.children) {
+            (Children::Internal(this), Children::Internal(next)) => redistribute(this, next),
+            (Children::Leaf(this), Children::Leaf(next)) => redistribute(this, next),
+            _ => checked::unreachable!("All the leafs must be in the same depth"),
+        };
+
+        self.refresh();
+        if !drained {
+            next.refresh();
+        }
+        drained
+    }
+
+    fn split_off_nth(&mut self, n: usize) -> Self {
+        fn split_children<N: Node<K, V, CAP>, K: Ord, V, const CAP: usize>(
+            children: &mut ArrayVec<N, CAP>,
+            n: usize,
+        ) -> ArrayVec<N, CAP> {
+            let (idx, n) = nth_child(children, n);
+            if n == 0 {
+                // The cut is between two children, where `idx` can't be 0 as the `n` isn't.
+                children[idx - 1].tail_mut().unlink_next();
+                return children.drain(idx..).collect();
+            }
+
+            let first = children[idx].split_off_nth(n);
+            core::iter::once(first)
+                .chain(children.drain(idx + 1..))
+                .collect()
+        }
+
+        let children = match &mut self.children {
+            Children::Internal(children) => Children::Internal(split_children(children, n)),
+            Children::Leaf(children) => Children::Leaf(split_children(children, n)),
+        };
+        self.refresh();
+        Internal::with_children(children)
+    }
+}
+
+fn head_tail<N: Node<K, V, CAP>, K, V, const CAP: usize>(
+    children: &mut [N],
+) -> (Leaf<K, V, CAP>, Leaf<K, V, CAP>) {
+    match children {
+        [first, .., last] => (
+            first.head_mut().shallow_clone(),
+            last.tail_mut().shallow_clone(),
+        ),
+        [only] => (
+            only.head_mut().shallow_clone(),
+            only.tail_mut().shallow_clone(),
+        ),
+        [] => checked::unreachable!("children shouldn't be empty"),
+    }
+}
+
+/// Rebalances the first two nodes if either of them is underfull.
+fn balance_first<N: Node<K, V, CAP>, K, V, const CAP: usize>(nodes: &mut ArrayVec<N, CAP>) {
+    let b = CAP.div_ceil(2);
+    if let [first, next, ..] = &mut nodes[..] {
+        if (first.occupancy() < b || next.occupancy() < b) && first.redistribute(next) {
+            nodes.remove(1);
+        }
+    }
+}
+
+/// Rebalances the last two nodes if either of them is underfull.
+fn balance_last<N: Node<K, V, CAP>, K, V, const CAP: usize>(nodes: &mut ArrayVec<N, CAP>) {
+    let b = CAP.div_ceil(2);
+    if let [.., prev, last] = &mut nodes[..] {
+        if (prev.occupancy() < b || last.occupancy() < b) && prev.redistribute(last) {
+            nodes.pop();
+        }
+    }
+}
+
+/// Returns the index of the child which contains the `n`-th entry,
+/// and the index of the entry within that child.
+fn nth_child<K: Ord, V, const CAP: usize>(
+    children: &[impl Node<K, V, CAP>],
+    mut n: usize,
+) -> (usize, usize) {
+    for (idx, child) in children.iter().enumerate() {
+        match n.checked_sub(child.len()) {
+            Some(rest) => n = rest,
+            None => return (idx, n),
+        }
+    }
+    checked::unreachable!("index should be less than the length")
 }
 
 fn find_idx<Q: Ord, K: Ord + Borrow<Q>, V, const CAP: usize>(
     slice: &[impl Node<K, V, CAP>],
     query: &Q,
 ) -> usize {
-    checked::assume!(slice.len() > 0);
+    checked::assume!(!slice.is_empty());
 
-    for (idx, node) in slice[1..].iter().enumerate() {
-        return match (*node.head().first()).borrow().cmp(query) {
-            Ordering::Greater => idx,
-            Ordering::Equal => idx + 1,
-            Ordering::Less => continue,
-        };
+    // The child right before the first one whose head is greater than the query.
+    slice[1..].partition_point(|node| (*node.head().first()).borrow() <= query)
+}
+
+#[test]
+fn find_idx_matches_linear_scan() {
+    use core::cmp::Ordering;
+
+    fn find_idx_linear<K: Ord, V, const CAP: usize>(slice: &[Leaf<K, V, CAP>], query: &K) -> usize {
+        for (idx, node) in slice[1..].iter().enumerate() {
+            return match (*node.head().first()).cmp(query) {
+                Ordering::Greater => idx,
+                Ordering::Equal => idx + 1,
+                Ordering::Less => continue,
+            };
+        }
+
+        slice.len() - 1
     }
 
-    slice.len() - 1
+    for len in 1..=31 {
+        let leaves: Vec<Leaf<u32, (), 31>> = (0..len)
+            .map(|i| Leaf::from_entries([(i * 4 + 2, ())].into_iter().collect()))
+            .collect();
+        for _ in 0..1000 {
+            let query = rand::random::<u32>() % (len * 4 + 4);
+            assert_eq!(
+                find_idx(&leaves, &query),
+                find_idx_linear(&leaves, &query),
+                "query {} in {} children",
+                query,
+                len
+            );
+        }
+    }
 }
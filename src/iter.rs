@@ -0,0 +1,1027 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::iter::FusedIterator;
+use core::ops::{Bound, RangeBounds};
+
+use arrayvec::ArrayVec;
+
+use crate::checked::{self, Ref, RefKey, RefMut};
+use crate::leaf::{Leaf, LeafRef};
+use crate::node::{Node, Target};
+use crate::BTreeMap;
+
+/// Iterator over the entries of the `BTreeMap` in ascending key order.
+pub struct Iter<'a, K, V, const CAP: usize> {
+    span: Span<'a, K, V, CAP, Ref<'a, [(K, V)]>>,
+    remaining: usize,
+}
+
+impl<'a, K: Ord, V, const CAP: usize> Iter<'a, K, V, CAP> {
+    pub(crate) fn new(map: &'a BTreeMap<K, V, CAP>) -> Self {
+        Iter {
+            span: Span::full(map),
+            remaining: map.length,
+        }
+    }
+
+    pub(crate) fn starting_at<Q>(map: &'a BTreeMap<K, V, CAP>, start: &Q) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+    {
+        Iter {
+            span: Span::range(map, (Bound::Included(start), Bound::Unbounded)),
+            remaining: map.length - map.rank(start),
+        }
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Iterator for Iter<'a, K, V, CAP> {
+    type Item = (Ref<'a, K>, Ref<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.span.next()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, const CAP: usize> DoubleEndedIterator for Iter<'a, K, V, CAP> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.span.next_back()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+}
+
+impl<'a, K, V, const CAP: usize> ExactSizeIterator for Iter<'a, K, V, CAP> {}
+
+impl<'a, K, V, const CAP: usize> FusedIterator for Iter<'a, K, V, CAP> {}
+
+/// Mutable iterator over the entries of the `BTreeMap` in ascending key order.
+pub struct IterMut<'a, K, V, const CAP: usize> {
+    span: Span<'a, K, V, CAP, RefMut<'a, [(K, V)]>>,
+    remaining: usize,
+}
+
+impl<'a, K: Ord, V, const CAP: usize> IterMut<'a, K, V, CAP> {
+    pub(crate) fn new(map: &'a mut BTreeMap<K, V, CAP>) -> Self {
+        IterMut {
+            remaining: map.length,
+            // SAFETY: The map is exclusively borrowed for `'a`, so the span is the only one
+            // which borrows its leafs.
+            span: Span::full(map),
+        }
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Iterator for IterMut<'a, K, V, CAP> {
+    type Item = (RefKey<'a, K>, RefMut<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.span.next()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K, V, const CAP: usize> DoubleEndedIterator for IterMut<'a, K, V, CAP> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let entry = self.span.next_back()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+}
+
+impl<'a, K, V, const CAP: usize> ExactSizeIterator for IterMut<'a, K, V, CAP> {}
+
+impl<'a, K, V, const CAP: usize> FusedIterator for IterMut<'a, K, V, CAP> {}
+
+/// Iterator over the keys of the `BTreeMap` in ascending order.
+pub struct Keys<'a, K, V, const CAP: usize> {
+    inner: Iter<'a, K, V, CAP>,
+}
+
+impl<'a, K, V, const CAP: usize> Keys<'a, K, V, CAP> {
+    pub(crate) fn new(inner: Iter<'a, K, V, CAP>) -> Self {
+        Keys { inner }
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Iterator for Keys<'a, K, V, CAP> {
+    type Item = Ref<'a, K>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const CAP: usize> DoubleEndedIterator for Keys<'a, K, V, CAP> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<'a, K, V, const CAP: usize> ExactSizeIterator for Keys<'a, K, V, CAP> {}
+
+impl<'a, K, V, const CAP: usize> FusedIterator for Keys<'a, K, V, CAP> {}
+
+/// Iterator over the values of the `BTreeMap` in ascending order of their keys.
+pub struct Values<'a, K, V, const CAP: usize> {
+    inner: Iter<'a, K, V, CAP>,
+}
+
+impl<'a, K, V, const CAP: usize> Values<'a, K, V, CAP> {
+    pub(crate) fn new(inner: Iter<'a, K, V, CAP>) -> Self {
+        Values { inner }
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Iterator for Values<'a, K, V, CAP> {
+    type Item = Ref<'a, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const CAP: usize> DoubleEndedIterator for Values<'a, K, V, CAP> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V, const CAP: usize> ExactSizeIterator for Values<'a, K, V, CAP> {}
+
+impl<'a, K, V, const CAP: usize> FusedIterator for Values<'a, K, V, CAP> {}
+
+/// Mutable iterator over the values of the `BTreeMap` in ascending order of their keys.
+pub struct ValuesMut<'a, K, V, const CAP: usize> {
+    inner: IterMut<'a, K, V, CAP>,
+}
+
+impl<'a, K, V, const CAP: usize> ValuesMut<'a, K, V, CAP> {
+    pub(crate) fn new(inner: IterMut<'a, K, V, CAP>) -> Self {
+        ValuesMut { inner }
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Iterator for ValuesMut<'a, K, V, CAP> {
+    type Item = RefMut<'a, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, const CAP: usize> DoubleEndedIterator for ValuesMut<'a, K, V, CAP> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, K, V, const CAP: usize> ExactSizeIterator for ValuesMut<'a, K, V, CAP> {}
+
+impl<'a, K, V, const CAP: usize> FusedIterator for ValuesMut<'a, K, V, CAP> {}
+
+/// Iterator over a sub-range of the entries of the `BTreeMap`.
+pub struct Range<'a, K, V, const CAP: usize> {
+    span: Span<'a, K, V, CAP, Ref<'a, [(K, V)]>>,
+}
+
+impl<'a, K: Ord, V, const CAP: usize> Range<'a, K, V, CAP> {
+    pub(crate) fn new<Q, R>(map: &'a BTreeMap<K, V, CAP>, range: R) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+        R: RangeBounds<Q>,
+    {
+        Range {
+            span: Span::range(map, range),
+        }
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Iterator for Range<'a, K, V, CAP> {
+    type Item = (Ref<'a, K>, Ref<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.span.next()
+    }
+
+    /// Takes the last entry from the end leaf found on creation, without walking the range.
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V, const CAP: usize> DoubleEndedIterator for Range<'a, K, V, CAP> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.span.next_back()
+    }
+}
+
+/// Mutable iterator over a sub-range of the entries of the `BTreeMap`.
+pub struct RangeMut<'a, K, V, const CAP: usize> {
+    span: Span<'a, K, V, CAP, RefMut<'a, [(K, V)]>>,
+}
+
+impl<'a, K: Ord, V, const CAP: usize> RangeMut<'a, K, V, CAP> {
+    pub(crate) fn new<Q, R>(map: &'a mut BTreeMap<K, V, CAP>, range: R) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+        R: RangeBounds<Q>,
+    {
+        RangeMut {
+            // SAFETY: Same as the `IterMut`.
+            span: Span::range(map, range),
+        }
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Iterator for RangeMut<'a, K, V, CAP> {
+    type Item = (RefKey<'a, K>, RefMut<'a, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.span.next()
+    }
+
+    /// Same as the `Range::last`.
+    fn last(mut self) -> Option<Self::Item> {
+        self.next_back()
+    }
+}
+
+impl<'a, K, V, const CAP: usize> DoubleEndedIterator for RangeMut<'a, K, V, CAP> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.span.next_back()
+    }
+}
+
+/// Entries of a leaf borrowed by the iterator, either shared or exclusive.
+trait Entries<'a, K, V>: Sized {
+    type Item;
+
+    /// # Safety
+    ///
+    /// The entries of the `leaf` mustn't be borrowed elsewhere while the returned ones are alive.
+    /// For the exclusive entries, the tree must be exclusively borrowed for `'a`.
+    unsafe fn borrow<const CAP: usize>(
+        leaf: LeafRef<'a, K, V, CAP>,
+        start: usize,
+        end: usize,
+    ) -> Self;
+
+    fn is_empty(&self) -> bool;
+
+    fn split_first(self) -> (Self::Item, Self);
+
+    fn split_last(self) -> (Self::Item, Self);
+}
+
+impl<'a, K, V> Entries<'a, K, V> for Ref<'a, [(K, V)]> {
+    type Item = (Ref<'a, K>, Ref<'a, V>);
+
+    unsafe fn borrow<const CAP: usize>(
+        leaf: LeafRef<'a, K, V, CAP>,
+        start: usize,
+        end: usize,
+    ) -> Self {
+        checked::map_ref(leaf.entries(), |entries| &entries[start..end])
+    }
+
+    fn is_empty(&self) -> bool {
+        <[(K, V)]>::is_empty(self)
+    }
+
+    fn split_first(self) -> (Self::Item, Self) {
+        let (entry, rest) = checked::split_ref(self, |entries| match entries.split_first() {
+            Some(split) => split,
+            None => checked::unreachable!("entries shouldn't be empty"),
+        });
+        let entry = checked::split_ref(entry, |entry| (&entry.0, &entry.1));
+        (entry, rest)
+    }
+
+    fn split_last(self) -> (Self::Item, Self) {
+        let (entry, rest) = checked::split_ref(self, |entries| match entries.split_last() {
+            Some(split) => split,
+            None => checked::unreachable!("entries shouldn't be empty"),
+        });
+        let entry = checked::split_ref(entry, |entry| (&entry.0, &entry.1));
+        (entry, rest)
+    }
+}
+
+impl<'a, K, V> Entries<'a, K, V> for RefMut<'a, [(K, V)]> {
+    type Item = (RefKey<'a, K>, RefMut<'a, V>);
+
+    unsafe fn borrow<const CAP: usize>(
+        leaf: LeafRef<'a, K, V, CAP>,
+        start: usize,
+        end: usize,
+    ) -> Self {
+        // SAFETY: Forwarded to the caller.
+        checked::map_mut(unsafe { leaf.entries_mut() }, |entries| {
+            &mut entries[start..end]
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        <[(K, V)]>::is_empty(self)
+    }
+
+    fn split_first(self) -> (Self::Item, Self) {
+        let (entry, rest) = checked::split_mut(self, |entries| match entries.split_first_mut() {
+            Some(split) => split,
+            None => checked::unreachable!("entries shouldn't be empty"),
+        });
+        let (key, value) = checked::split_mut(entry, |entry| (&mut entry.0, &mut entry.1));
+        ((checked::mut_to_key(key), value), rest)
+    }
+
+    fn split_last(self) -> (Self::Item, Self) {
+        let (entry, rest) = checked::split_mut(self, |entries| match entries.split_last_mut() {
+            Some(split) => split,
+            None => checked::unreachable!("entries shouldn't be empty"),
+        });
+        let (key, value) = checked::split_mut(entry, |entry| (&mut entry.0, &mut entry.1));
+        ((checked::mut_to_key(key), value), rest)
+    }
+}
+
+/// Position within the leaf chain.
+type Cursor<'a, K, V, const CAP: usize> = (LeafRef<'a, K, V, CAP>, usize);
+
+/// Entries between two positions of the leaf chain, consumed from both ends.
+///
+/// Each leaf is borrowed only once it's reached from either end,
+/// so the leafs between `front` and `back` are never borrowed twice.
+struct Span<'a, K, V, const CAP: usize, E> {
+    front: Option<E>,
+    back: Option<E>,
+    /// The first and the last leafs not borrowed yet, with the start and the end index within them.
+    unvisited: Option<(Cursor<'a, K, V, CAP>, Cursor<'a, K, V, CAP>)>,
+}
+
+impl<'a, K: Ord, V, const CAP: usize, E: Entries<'a, K, V>> Span<'a, K, V, CAP, E> {
+    fn empty() -> Self {
+        Span {
+            front: None,
+            back: None,
+            unvisited: None,
+        }
+    }
+
+    fn full(map: &'a BTreeMap<K, V, CAP>) -> Self {
+        Self::range::<K, _>(map, ..)
+    }
+
+    fn range<Q, R>(map: &'a BTreeMap<K, V, CAP>, range: R) -> Self
+    where
+        K: Borrow<Q>,
+        Q: Ord,
+        R: RangeBounds<Q>,
+    {
+        check_range(&range);
+
+        let root = match &map.root {
+            Some(root) => root,
+            None => return Self::empty(),
+        };
+
+        // Skip the descents if the range is after the last key or before the first key.
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+        let after_last = match range.start_bound() {
+            Bound::Included(query) => K::borrow(&root.tail().last()) < query,
+            Bound::Excluded(query) => K::borrow(&root.tail().last()) <= query,
+            Bound::Unbounded => false,
+        };
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+        let before_first = match range.end_bound() {
+            Bound::Included(query) => K::borrow(&root.head().first()) > query,
+            Bound::Excluded(query) => K::borrow(&root.head().first()) >= query,
+            Bound::Unbounded => false,
+        };
+        if after_last || before_first {
+            return Self::empty();
+        }
+
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+        let start = match range.start_bound() {
+            Bound::Included(query) | Bound::Excluded(query) => {
+                let leaf = root.leaf_for(query).leaf_ref();
+                (leaf, start_idx(&leaf.entries(), range.start_bound()))
+            }
+            Bound::Unbounded => (root.head().leaf_ref(), 0),
+        };
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+        let end = match range.end_bound() {
+            Bound::Included(query) | Bound::Excluded(query) => {
+                let leaf = root.leaf_for(query).leaf_ref();
+                (leaf, end_idx(&leaf.entries(), range.end_bound()))
+            }
+            Bound::Unbounded => {
+                let leaf = root.tail().leaf_ref();
+                (leaf, leaf.entries().len())
+            }
+        };
+
+        Span {
+            front: None,
+            back: None,
+            unvisited: Some((start, end)),
+        }
+    }
+}
+
+impl<'a, K, V, const CAP: usize, E: Entries<'a, K, V>> Span<'a, K, V, CAP, E> {
+    fn next(&mut self) -> Option<E::Item> {
+        loop {
+            if let Some(entries) = self.front.take() {
+                if !entries.is_empty() {
+                    let (item, rest) = entries.split_first();
+                    self.front = Some(rest);
+                    return Some(item);
+                }
+            }
+
+            let ((first, start), (last, end)) = match self.unvisited.take() {
+                Some(unvisited) => unvisited,
+                // Every leaf is borrowed, the remaining entries are all in the back.
+                None => return self.next_in_back(),
+            };
+
+            // SAFETY: The leafs in `unvisited` are not borrowed by the `front` or the `back` yet,
+            // and each of them leaves it once borrowed. The `IterMut` and the `RangeMut`
+            // create the span from an exclusively borrowed map.
+            if first.ptr_eq(last) {
+                self.front = Some(unsafe { E::borrow(first, start.min(end), end) });
+            } else {
+                // Links are read before borrowing the entries,
+                // as the leaf can't be read while its entries are mutably borrowed.
+                self.unvisited = first.next().map(|next| ((next, 0), (last, end)));
+                let len = first.entries().len();
+                self.front = Some(unsafe { E::borrow(first, start, len) });
+            }
+        }
+    }
+
+    fn next_back(&mut self) -> Option<E::Item> {
+        loop {
+            if let Some(entries) = self.back.take() {
+                if !entries.is_empty() {
+                    let (item, rest) = entries.split_last();
+                    self.back = Some(rest);
+                    return Some(item);
+                }
+            }
+
+            let ((first, start), (last, end)) = match self.unvisited.take() {
+                Some(unvisited) => unvisited,
+                None => return self.next_back_in_front(),
+            };
+
+            // SAFETY: Same as the `next`.
+            if first.ptr_eq(last) {
+                self.back = Some(unsafe { E::borrow(last, start.min(end), end) });
+            } else {
+                self.unvisited = last
+                    .prev()
+                    .map(|prev| ((first, start), (prev, prev.entries().len())));
+                self.back = Some(unsafe { E::borrow(last, 0, end) });
+            }
+        }
+    }
+
+    fn next_in_back(&mut self) -> Option<E::Item> {
+        let entries = self.back.take()?;
+        if entries.is_empty() {
+            return None;
+        }
+        let (item, rest) = entries.split_first();
+        self.back = Some(rest);
+        Some(item)
+    }
+
+    fn next_back_in_front(&mut self) -> Option<E::Item> {
+        let entries = self.front.take()?;
+        if entries.is_empty() {
+            return None;
+        }
+        let (item, rest) = entries.split_last();
+        self.front = Some(rest);
+        Some(item)
+    }
+}
+
+/// Panics if the range is inverted, same as the `std::collections::BTreeMap`.
+pub(crate) fn check_range<Q: Ord, R: RangeBounds<Q>>(range: &R) {
+    match (range.start_bound(), range.end_bound()) {
+        (Bound::Excluded(start), Bound::Excluded(end)) if start == end => {
+            panic!("range start and end are equal and excluded in BTreeMap")
+        }
+        (
+            Bound::Included(start) | Bound::Excluded(start),
+            Bound::Included(end) | Bound::Excluded(end),
+        ) if start > end => panic!("range start is greater than range end in BTreeMap"),
+        _ => {}
+    }
+}
+
+/// Returns the number of the entries before the start `bound`.
+pub(crate) fn start_idx<K: Borrow<Q>, V, Q: Ord>(entries: &[(K, V)], bound: Bound<&Q>) -> usize {
+    entries.partition_point(|(key, _)| match bound {
+        Bound::Included(query) => key.borrow() < query,
+        Bound::Excluded(query) => key.borrow() <= query,
+        Bound::Unbounded => false,
+    })
+}
+
+/// Returns the number of the entries before the end `bound`.
+pub(crate) fn end_idx<K: Borrow<Q>, V, Q: Ord>(entries: &[(K, V)], bound: Bound<&Q>) -> usize {
+    entries.partition_point(|(key, _)| match bound {
+        Bound::Included(query) => key.borrow() <= query,
+        Bound::Excluded(query) => key.borrow() < query,
+        Bound::Unbounded => true,
+    })
+}
+
+/// Owning iterator over the entries of the `BTreeMap` in ascending key order.
+pub struct IntoIter<K, V, const CAP: usize> {
+    leaves: VecDeque<Leaf<K, V, CAP>>,
+    front: Option<arrayvec::IntoIter<(K, V), CAP>>,
+    back: Option<arrayvec::IntoIter<(K, V), CAP>>,
+    remaining: usize,
+}
+
+impl<K, V, const CAP: usize> IntoIter<K, V, CAP> {
+    pub(crate) fn new(leaves: Vec<Leaf<K, V, CAP>>, length: usize) -> Self {
+        IntoIter {
+            leaves: leaves.into(),
+            front: None,
+            back: None,
+            remaining: length,
+        }
+    }
+}
+
+impl<K, V, const CAP: usize> Iterator for IntoIter<K, V, CAP> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.front.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(entry);
+            }
+
+            match self.leaves.pop_front() {
+                Some(leaf) => self.front = Some(ArrayVec::into_iter(leaf.into_entries())),
+                None => {
+                    // The remaining entries are in the leaf the back is draining.
+                    let entry = self.back.as_mut()?.next()?;
+                    self.remaining -= 1;
+                    return Some(entry);
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, const CAP: usize> DoubleEndedIterator for IntoIter<K, V, CAP> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.back.as_mut().and_then(DoubleEndedIterator::next_back) {
+                self.remaining -= 1;
+                return Some(entry);
+            }
+
+            match self.leaves.pop_back() {
+                Some(leaf) => self.back = Some(ArrayVec::into_iter(leaf.into_entries())),
+                None => {
+                    // The remaining entries are in the leaf the front is draining.
+                    let entry = self.front.as_mut()?.next_back()?;
+                    self.remaining -= 1;
+                    return Some(entry);
+                }
+            }
+        }
+    }
+}
+
+impl<K, V, const CAP: usize> ExactSizeIterator for IntoIter<K, V, CAP> {}
+
+impl<K, V, const CAP: usize> FusedIterator for IntoIter<K, V, CAP> {}
+
+/// Iterator which removes and yields the entries matching the predicate,
+/// created by `BTreeMap::extract_if`.
+///
+/// Entries not visited yet are kept in the map if the iterator is dropped early.
+pub struct ExtractIf<'a, K, V, const CAP: usize, F> {
+    map: &'a mut BTreeMap<K, V, CAP>,
+    /// Number of the entries visited and kept, which is the rank of the next entry to visit.
+    kept: usize,
+    pred: F,
+}
+
+impl<'a, K: Ord, V, const CAP: usize, F> ExtractIf<'a, K, V, CAP, F> {
+    pub(crate) fn new(map: &'a mut BTreeMap<K, V, CAP>, pred: F) -> Self {
+        ExtractIf { map, kept: 0, pred }
+    }
+}
+
+impl<'a, K, V, const CAP: usize, F> Iterator for ExtractIf<'a, K, V, CAP, F>
+where
+    K: Ord,
+    F: FnMut(&K, &mut V) -> bool,
+{
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.kept < self.map.len() {
+            #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+            let extract = {
+                let (key, mut value) = self.map.select_mut(self.kept);
+                (self.pred)(&key, &mut value)
+            };
+            if extract {
+                let (key, value, _) = self.map.remove_target(Target::<K>::Nth(self.kept))?;
+                return Some((key, value));
+            }
+            self.kept += 1;
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.len() - self.kept))
+    }
+}
+
+#[test]
+fn into_iter_rev_and_mixed_drain() {
+    let nums: Vec<u32> = std::iter::repeat_with(rand::random).take(4096).collect();
+    let mut sorted = nums.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let build = || {
+        let mut map = BTreeMap::<_, _, 5>::new();
+        for &n in &nums {
+            map.insert(n, n);
+        }
+        map
+    };
+
+    let rev: Vec<_> = build().into_iter().rev().map(|(k, _)| k).collect();
+    assert!(rev.iter().eq(sorted.iter().rev()));
+
+    for _ in 0..16 {
+        let mut iter = build().into_iter();
+        let (mut front, mut back) = (Vec::new(), Vec::new());
+        loop {
+            let entry = match rand::random::<bool>() {
+                true => iter.next().map(|entry| front.push(entry.0)),
+                false => iter.next_back().map(|entry| back.push(entry.0)),
+            };
+            assert_eq!(iter.size_hint().0, sorted.len() - front.len() - back.len());
+            if entry.is_none() {
+                break;
+            }
+        }
+        assert!(iter.next().is_none() && iter.next_back().is_none());
+
+        front.extend(back.into_iter().rev());
+        assert_eq!(front, sorted);
+    }
+}
+
+#[test]
+fn iter_matches_std_btreemap() {
+    let mut map = BTreeMap::<u32, u32, 7>::new();
+    let mut std_map = std::collections::BTreeMap::new();
+    for _ in 0..100_000 {
+        let (key, value) = rand::random();
+        map.insert(key, value);
+        std_map.insert(key, value);
+    }
+
+    let iter = map.iter();
+    assert_eq!(iter.size_hint(), (map.len(), Some(map.len())));
+    assert_eq!(iter.count(), map.len());
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq(std_map.iter().map(|(k, v)| (*k, *v))));
+}
+
+#[test]
+fn into_iter_drains_sorted() {
+    let mut map = BTreeMap::<u32, String, 5>::new();
+    for _ in 0..10_000 {
+        let key: u32 = rand::random::<u32>() % 20_000;
+        map.insert(key, key.to_string());
+    }
+    let len = map.len();
+
+    let entries: Vec<(u32, String)> = map.into_iter().collect();
+    assert_eq!(entries.len(), len);
+    assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(entries.iter().all(|(k, v)| k.to_string() == *v));
+}
+
+#[test]
+fn keys_and_values_follow_key_order() {
+    let mut map = BTreeMap::<u32, u64, 5>::new();
+    let mut keys: Vec<u32> = (0..1000).map(|i| i * 7919 % 1000).collect();
+    for &key in &keys {
+        map.insert(key, key as u64 * 2);
+    }
+    keys.sort_unstable();
+
+    assert_eq!(map.keys().size_hint(), (1000, Some(1000)));
+    let mut values = map.values();
+    values.next();
+    assert_eq!(values.size_hint(), (999, Some(999)));
+
+    assert_eq!(map.keys().count(), keys.len());
+    assert!(map.keys().zip(&keys).all(|(k, expected)| *k == *expected));
+    assert!(map.values().zip(&keys).all(|(v, k)| *v == *k as u64 * 2));
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn values_mut_doubles_in_place() {
+    let mut map = BTreeMap::<u32, u64, 5>::new();
+    for key in (0..1000).map(|i| i * 7919 % 1000) {
+        map.insert(key, key as u64);
+    }
+
+    assert_eq!(map.values_mut().size_hint(), (1000, Some(1000)));
+    for mut value in map.values_mut() {
+        *value *= 2;
+    }
+    for (key, mut value) in map.iter_mut() {
+        *value += *key as u64;
+    }
+
+    assert!((0..1000).all(|key| *map.get(&key).unwrap() == key as u64 * 3));
+}
+
+#[test]
+fn iter_rev_and_mixed_ends() {
+    let nums: Vec<u32> = std::iter::repeat_with(rand::random).take(4096).collect();
+    let mut map = BTreeMap::<u32, u32, 5>::new();
+    for &n in &nums {
+        map.insert(n, n);
+    }
+    for &n in nums.iter().step_by(3) {
+        map.remove(&n);
+    }
+
+    let forward: Vec<_> = map.iter().map(|(k, _)| *k).collect();
+    let backward: Vec<_> = map.iter().rev().map(|(k, _)| *k).collect();
+    assert_eq!(forward.len(), map.len());
+    assert!(backward.iter().eq(forward.iter().rev()));
+
+    for _ in 0..16 {
+        let mut iter = map.iter_mut();
+        let (mut front, mut back) = (Vec::new(), Vec::new());
+        loop {
+            let entry = match rand::random::<bool>() {
+                true => iter.next().map(|(k, _)| front.push(*k)),
+                false => iter.next_back().map(|(k, _)| back.push(*k)),
+            };
+            assert_eq!(iter.size_hint().0, forward.len() - front.len() - back.len());
+            if entry.is_none() {
+                break;
+            }
+        }
+        assert!(front.iter().chain(back.iter().rev()).eq(forward.iter()));
+    }
+}
+
+#[test]
+fn range_matches_std_btreemap() {
+    let mut map = BTreeMap::<u32, u32, 5>::new();
+    let mut std_map = std::collections::BTreeMap::new();
+    for key in (0..2000)
+        .map(|i| i * 7919 % 2000)
+        .filter(|key| key % 3 != 0)
+    {
+        map.insert(key, key);
+        std_map.insert(key, key);
+    }
+
+    macro_rules! check {
+        ($($range:expr),*) => {$(
+            let res: Vec<_> = map.range($range).map(|(k, _)| *k).collect();
+            let expected: Vec<_> = std_map.range($range).map(|(k, _)| *k).collect();
+            assert_eq!(res, expected, "{:?}", $range);
+        )*};
+    }
+    check!(
+        300..900,
+        299..=901,
+        300..300,
+        300..=300,
+        ..900,
+        ..=0,
+        1200..,
+        2000..,
+        (Bound::Excluded(300), Bound::Included(900))
+    );
+}
+
+#[test]
+#[should_panic(expected = "range start is greater than range end in BTreeMap")]
+fn range_panics_on_inverted_bounds() {
+    let map = BTreeMap::<u32, u32, 5>::new();
+    let (start, end) = (2, 1);
+    map.range(start..end);
+}
+
+#[test]
+fn iter_len_counts_down_to_zero() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i, i)).collect();
+
+    {
+        let mut iter = map.iter();
+        for remaining in (0..1000).rev() {
+            assert!(iter.next().is_some());
+            assert_eq!(iter.len(), remaining);
+        }
+        assert!(iter.next().is_none() && iter.next_back().is_none());
+
+        let mut iter = map.keys();
+        assert_eq!(iter.len(), 1000);
+        assert!(iter.next_back().is_some() && iter.next().is_some());
+        assert_eq!(iter.len(), 998);
+        iter.by_ref().for_each(drop);
+        assert_eq!(iter.len(), 0);
+        assert!(iter.next().is_none() && iter.next().is_none());
+    }
+
+    assert_eq!(map.values().skip(10).len(), 990);
+    assert_eq!(map.values_mut().len(), 1000);
+    assert_eq!(map.iter_mut().rev().skip(1).len(), 999);
+
+    let mut iter = map.into_iter();
+    while iter.len() > 0 {
+        let before = iter.len();
+        assert!(iter.next().is_some());
+        assert_eq!(iter.len(), before - 1);
+    }
+    assert!(iter.next().is_none() && iter.next_back().is_none());
+}
+
+#[test]
+fn range_descends_once_and_follows_links() {
+    use crate::test_util::{comparisons, Counted};
+
+    let map: BTreeMap<Counted, u32, 5> = (100..10_100).map(|i| (Counted(i), i)).collect();
+    comparisons();
+
+    // Out of range on either side is decided by a single comparison with the first or last key.
+    assert_eq!(map.range(Counted(20_000)..).count(), 0);
+    assert_eq!(comparisons(), 1);
+    assert_eq!(map.range(Counted(10_099)..).count(), 1);
+    assert!(comparisons() > 1);
+    assert_eq!(map.range(..Counted(100)).count(), 0);
+    assert_eq!(comparisons(), 1);
+    assert_eq!(map.range(..=Counted(99)).rev().count(), 0);
+    assert_eq!(comparisons(), 1);
+    assert_eq!(
+        map.range((Bound::Excluded(Counted(10_099)), Bound::Unbounded))
+            .count(),
+        0
+    );
+    assert_eq!(comparisons(), 1);
+
+    // One descent for each bound, then the iteration only follows the leaf links.
+    let mut range = map.range(Counted(3000)..Counted(8000));
+    let descents = comparisons();
+    assert!(descents <= 2 + 2 * (map.height() + 1) * 5, "{}", descents);
+    assert_eq!(range.next().map(|(_, v)| *v), Some(3000));
+    assert_eq!(range.next_back().map(|(_, v)| *v), Some(7999));
+    assert_eq!(range.count(), 4998);
+    assert_eq!(comparisons(), 0);
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn range_last_matches_linear_scan() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..2000).map(|i| (i * 3, i)).collect();
+
+    for x in (0..6100).step_by(7) {
+        let scanned = map.iter().map(|(k, _)| *k).filter(|k| *k <= x).max();
+        assert_eq!(map.range(..=x).last().map(|(k, _)| *k), scanned);
+        let scanned = map.iter().map(|(k, _)| *k).filter(|k| *k < x).max();
+        assert_eq!(map.range(..x).last().map(|(k, _)| *k), scanned);
+        let scanned = map
+            .iter()
+            .map(|(k, _)| *k)
+            .filter(|k| (x..x + 10).contains(k))
+            .max();
+        assert_eq!(map.range(x..x + 10).last().map(|(k, _)| *k), scanned);
+    }
+
+    // Entries taken from the back are not yielded again.
+    let mut range = map.range(10..20);
+    assert_eq!(range.next_back().map(|(k, _)| *k), Some(18));
+    assert_eq!(range.last().map(|(k, _)| *k), Some(15));
+    let mut range = map.range(10..13);
+    assert_eq!(range.next().map(|(k, _)| *k), Some(12));
+    assert!(range.last().is_none());
+
+    if let Some((_, mut value)) = map.range_mut(..=100).last() {
+        *value = 0;
+    }
+    assert_eq!(*map.get(&99).unwrap(), 0);
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn range_mut_leaves_outside_untouched() {
+    let mut map = BTreeMap::<u32, u32, 5>::new();
+    for key in 0..500 {
+        map.insert(key, key);
+    }
+
+    for (key, mut value) in map.range_mut(100..=200) {
+        *value = *key * 10;
+    }
+    for (_, mut value) in map.range_mut(450..).rev() {
+        *value = 0;
+    }
+
+    for (key, value) in map.iter() {
+        let expected = match *key {
+            100..=200 => *key * 10,
+            450.. => 0,
+            _ => *key,
+        };
+        assert_eq!(*value, expected);
+    }
+}
+
+#[test]
+fn extract_if_removes_matching_entries() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i, i)).collect();
+
+    let extracted: Vec<_> = map
+        .extract_if(|k, v| {
+            *v += 1;
+            k % 2 == 1
+        })
+        .collect();
+    assert!(extracted
+        .iter()
+        .copied()
+        .eq((0..500).map(|i| (i * 2 + 1, i * 2 + 2))));
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq((0..500).map(|i| (i * 2, i * 2 + 1))));
+    map.check_invariants();
+
+    // Entries after the early drop are kept untouched.
+    let mut iter = map.extract_if(|k, _| k % 4 == 0);
+    assert_eq!(iter.next(), Some((0, 1)));
+    assert_eq!(iter.next(), Some((4, 5)));
+    assert_eq!(map.len(), 498);
+    assert!(map.contains_key(&8));
+    map.check_invariants();
+
+    assert_eq!(map.extract_if(|_, _| true).count(), 498);
+    assert!(map.is_empty());
+    map.check_invariants();
+}
@@ -1,16 +1,24 @@
-use std::borrow::Borrow;
-use std::cmp::{Ord, Ordering};
-use std::fmt;
-use std::mem;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cmp::Ord;
+use core::fmt;
+use core::mem;
 
 use arrayvec::ArrayVec;
 
-use crate::checked::{self, RcCell, Ref, RefKey, RefMut, WeakCell};
-use crate::insert_or_split;
-use crate::node::Node;
+use crate::checked::{self, CellRef, RcCell, Ref, RefKey, RefMut, WeakCell};
+use crate::node::{Node, Route, Spare, Target};
+use crate::AllocError;
+use crate::{insert_or_split, redistribute};
 
 pub(crate) struct Leaf<K, V, const CAP: usize>(RcCell<LeafData<K, V, CAP>>);
 
+/// Weak handle to a leaf which stays valid while the tree is restructured.
+pub(crate) struct LeafWeak<K, V, const CAP: usize>(WeakCell<LeafData<K, V, CAP>>);
+
+/// Borrowed handle to a leaf which can follow the links between leafs.
+pub(crate) struct LeafRef<'a, K, V, const CAP: usize>(CellRef<'a, LeafData<K, V, CAP>>);
+
 #[derive(Debug)]
 struct LeafData<K, V, const CAP: usize> {
     entries: ArrayVec<(K, V), CAP>,
@@ -29,14 +37,6 @@ impl<K: Clone, V: Clone, const CAP: usize> Clone for LeafData<K, V, CAP> {
 }
 
 impl<K, V, const CAP: usize> Leaf<K, V, CAP> {
-    pub fn new(entry: (K, V)) -> Self {
-        Leaf(RcCell::new(LeafData {
-            entries: [entry].into_iter().collect(),
-            prev: None,
-            next: None,
-        }))
-    }
-
     // pub fn ptr_eq(&self, rhs: &Self) -> bool {
     //     RcCell::ptr_eq(&self.0, &rhs.0)
     // }
@@ -55,17 +55,210 @@ impl<K, V, const CAP: usize> Leaf<K, V, CAP> {
         })
     }
 
+    pub fn from_entries(entries: ArrayVec<(K, V), CAP>) -> Self {
+        Leaf(RcCell::new(LeafData {
+            entries,
+            prev: None,
+            next: None,
+        }))
+    }
+
+    /// Same as the `from_entries`, but returns `AllocError` instead of aborting.
+    pub fn try_from_entries(entries: ArrayVec<(K, V), CAP>) -> Result<Self, AllocError> {
+        Ok(Leaf(RcCell::try_new(LeafData {
+            entries,
+            prev: None,
+            next: None,
+        })?))
+    }
+
+    pub fn first_entry(&self) -> (Ref<'_, K>, Ref<'_, V>) {
+        let entry = checked::map_ref(self.0.get(), |this| match this.entries.first() {
+            Some(entry) => entry,
+            None => checked::unreachable!("Leaf node can't be empty"),
+        });
+        checked::split_ref(entry, |entry| (&entry.0, &entry.1))
+    }
+
+    pub fn last_entry(&self) -> (Ref<'_, K>, Ref<'_, V>) {
+        let entry = checked::map_ref(self.0.get(), |this| match this.entries.last() {
+            Some(entry) => entry,
+            None => checked::unreachable!("Leaf node can't be empty"),
+        });
+        checked::split_ref(entry, |entry| (&entry.0, &entry.1))
+    }
+
     pub fn shallow_clone(&mut self) -> Self {
         Self(RcCell::shallow_clone(&mut self.0))
     }
+
+    pub fn downgrade(&mut self) -> LeafWeak<K, V, CAP> {
+        LeafWeak(self.0.downgrade())
+    }
+
+    /// Points the `prev` link of the next leaf back to this leaf.
+    /// Does nothing if the next leaf is already dropped.
+    fn relink_next(&mut self) {
+        let this_weak = self.0.downgrade();
+        let next = self.0.get_mut().next.as_mut().and_then(WeakCell::upgrade);
+        if let Some(mut next) = next {
+            next.get_mut().prev = Some(this_weak);
+        }
+    }
+
+    /// Links the `next` right after this leaf.
+    pub fn link_next(&mut self, next: &mut Self) {
+        self.0.get_mut().next = Some(next.0.downgrade());
+        next.0.get_mut().prev = Some(self.0.downgrade());
+    }
+
+    /// Cuts the link between this leaf and the next one.
+    pub fn unlink_next(&mut self) {
+        let next = self
+            .0
+            .get_mut()
+            .next
+            .take()
+            .and_then(|mut next| next.upgrade());
+        if let Some(mut next) = next {
+            next.get_mut().prev = None;
+        }
+    }
+
+    pub fn leaf_ref(&self) -> LeafRef<'_, K, V, CAP> {
+        LeafRef(self.0.cell_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.get().entries.len()
+    }
+
+    /// Takes the entries out of the leaf, which must not be shared.
+    pub fn into_entries(self) -> ArrayVec<(K, V), CAP> {
+        match self.0.try_unwrap() {
+            Ok(data) => data.entries,
+            Err(_) => checked::unreachable!("leaf shouldn't be shared"),
+        }
+    }
+
+    pub fn entries_mut(&mut self) -> RefMut<'_, ArrayVec<(K, V), CAP>> {
+        checked::map_mut(self.0.get_mut(), |this| &mut this.entries)
+    }
+}
+
+/// Merges or rebalances the underfull leafs so every leaf has at least `CAP.div_ceil(2)` entries
+/// unless there's only one leaf, then links them in order.
+pub(crate) fn rebalance_leaves<K: Ord, V, const CAP: usize>(
+    leaves: Vec<Leaf<K, V, CAP>>,
+) -> Vec<Leaf<K, V, CAP>> {
+    let b = CAP.div_ceil(2);
+    let mut out: Vec<Leaf<K, V, CAP>> = Vec::with_capacity(leaves.len());
+
+    for mut leaf in leaves {
+        if leaf.len() == 0 {
+            continue;
+        }
+        if let Some(last) = out.last_mut() {
+            if (last.len() < b || leaf.len() < b) && last.redistribute(&mut leaf) {
+                continue;
+            }
+        }
+        out.push(leaf);
+    }
+
+    if let [.., prev, last] = &mut out[..] {
+        if last.len() < b && prev.redistribute(last) {
+            out.pop();
+        }
+    }
+
+    link_leaves(&mut out);
+    out
+}
+
+/// Links the leafs in the order of the slice.
+pub(crate) fn link_leaves<K, V, const CAP: usize>(leaves: &mut [Leaf<K, V, CAP>]) {
+    let mut prev: Option<&mut Leaf<K, V, CAP>> = None;
+
+    for leaf in leaves {
+        let prev_weak = prev.as_mut().map(|prev| prev.0.downgrade());
+        if let Some(prev) = prev {
+            prev.0.get_mut().next = Some(leaf.0.downgrade());
+        }
+
+        #[cfg_attr(feature = "unchecked", allow(unused_mut))]
+        let mut this = leaf.0.get_mut();
+        this.prev = prev_weak;
+        this.next = None;
+        #[cfg_attr(feature = "unchecked", allow(dropping_references))]
+        drop(this);
+
+        prev = Some(leaf);
+    }
+}
+
+impl<K, V, const CAP: usize> LeafWeak<K, V, CAP> {
+    /// # Safety
+    ///
+    /// The leaf must be kept alive by the tree which is borrowed for `'a`.
+    pub unsafe fn deref<'a>(&self) -> Option<LeafRef<'a, K, V, CAP>> {
+        unsafe { self.0.deref() }.map(LeafRef)
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Clone for LeafRef<'a, K, V, CAP> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, K, V, const CAP: usize> Copy for LeafRef<'a, K, V, CAP> {}
+
+impl<'a, K, V, const CAP: usize> LeafRef<'a, K, V, CAP> {
+    pub fn entries(self) -> Ref<'a, [(K, V)]> {
+        checked::map_ref(self.0.get(), |this| &this.entries[..])
+    }
+
+    /// # Safety
+    ///
+    /// The caller must have the exclusive access to the tree which owns the leaf,
+    /// and mustn't hold any other guard of the same leaf while the returned one is alive.
+    pub unsafe fn entries_mut(self) -> RefMut<'a, [(K, V)]> {
+        // SAFETY: Forwarded to the caller.
+        checked::map_mut(unsafe { self.0.get_mut() }, |this| &mut this.entries[..])
+    }
+
+    pub fn entry(self, idx: usize) -> (Ref<'a, K>, Ref<'a, V>) {
+        let entry = checked::map_ref(self.0.get(), |this| &this.entries[idx]);
+        checked::split_ref(entry, |entry| (&entry.0, &entry.1))
+    }
+
+    pub fn ptr_eq(self, rhs: Self) -> bool {
+        self.0.ptr_eq(rhs.0)
+    }
+
+    pub fn next(self) -> Option<Self> {
+        let this = self.0.get();
+        let next = this.next.as_ref()?;
+        // SAFETY: Leafs are only linked to the other leafs of the same tree,
+        // which is borrowed for `'a` so none of them can be dropped meanwhile.
+        unsafe { next.deref() }.map(LeafRef)
+    }
+
+    pub fn prev(self) -> Option<Self> {
+        let this = self.0.get();
+        let prev = this.prev.as_ref()?;
+        // SAFETY: Same as the `next`.
+        unsafe { prev.deref() }.map(LeafRef)
+    }
 }
 
-// impl<K: Clone, V: Clone, const CAP: usize> Leaf<K, V, CAP> {
-//     /// Deep clone doesn't preserve links between nodes.
-//     pub fn deep_clone(&self) -> Self {
-//         Self(RcCell::deep_clone(&self.0))
-//     }
-// }
+impl<'a, K: Clone, V: Clone, const CAP: usize> LeafRef<'a, K, V, CAP> {
+    /// Deep clone doesn't preserve links between nodes.
+    pub fn deep_clone(self) -> Leaf<K, V, CAP> {
+        Leaf(RcCell::new(self.0.get().clone()))
+    }
+}
 
 impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Leaf<K, V, CAP> {
     fn head(&self) -> &Leaf<K, V, CAP> {
@@ -84,11 +277,39 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Leaf<K, V, CAP> {
         self
     }
 
+    fn len(&self) -> usize {
+        Leaf::len(self)
+    }
+
+    fn rank<Q: Ord>(&self, query: &Q) -> usize
+    where
+        K: Borrow<Q>,
+    {
+        match query_idx(&self.0.get().entries, query) {
+            Ok(idx) | Err(idx) => idx,
+        }
+    }
+
+    fn partition_point<F: FnMut(&K) -> bool>(&self, pred: &mut F) -> usize {
+        self.0.get().entries.partition_point(|(key, _)| pred(key))
+    }
+
+    fn select(&self, n: usize) -> (Ref<'_, K>, Ref<'_, V>) {
+        self.get_at(&[], n)
+    }
+
+    fn select_mut(&mut self, n: usize) -> (RefKey<'_, K>, RefMut<'_, V>) {
+        let entry = checked::map_mut(self.0.get_mut(), |this| &mut this.entries[n]);
+        let (key, value) = checked::split_mut(entry, |entry| (&mut entry.0, &mut entry.1));
+        (checked::mut_to_key(key), value)
+    }
+
     fn get<Q: Ord>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
     where
         K: Borrow<Q>,
     {
         let entries = checked::map_ref(self.0.get(), |this| &this.entries);
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
         let idx = query_idx(&entries, query).ok()?;
         let entry = checked::map_ref(entries, |entries| &entries[idx]);
         Some(checked::split_ref(entry, |entry| (&entry.0, &entry.1)))
@@ -99,17 +320,62 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Leaf<K, V, CAP> {
         K: Borrow<Q>,
     {
         let entries = checked::map_mut(self.0.get_mut(), |this| &mut this.entries);
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
         let idx = query_idx(&entries, query).ok()?;
         let entry = checked::map_mut(entries, |entries| &mut entries[idx]);
         let (key, value) = checked::split_mut(entry, |entry| (&mut entry.0, &mut entry.1));
         Some((checked::mut_to_key(key), value))
     }
 
-    fn insert(&mut self, new_entry: (K, V)) -> (Option<(K, V)>, Option<Self>) {
+    fn get_at(&self, path: &[usize], idx: usize) -> (Ref<'_, K>, Ref<'_, V>) {
+        checked::assume!(path.is_empty());
+        let entry = checked::map_ref(self.0.get(), |this| &this.entries[idx]);
+        checked::split_ref(entry, |entry| (&entry.0, &entry.1))
+    }
+
+    fn leaf_at_mut(&mut self, path: &[usize]) -> &mut Leaf<K, V, CAP> {
+        checked::assume!(path.is_empty());
+        self
+    }
+
+    fn locate<Q: Ord>(&self, query: &Q, _path: &mut Vec<usize>) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+    {
+        query_idx(&self.0.get().entries, query)
+    }
+
+    fn locate_nth(&self, n: usize, _path: &mut Vec<usize>) -> usize {
+        n
+    }
+
+    fn rank_at(&self, path: &[usize], idx: usize) -> usize {
+        checked::assume!(path.is_empty());
+        idx
+    }
+
+    fn insert(
+        &mut self,
+        new_entry: (K, V),
+        route: Route<'_>,
+        spare: &mut Spare<K, V, CAP>,
+    ) -> (Option<(K, V)>, Option<Self>) {
+        #[cfg_attr(feature = "unchecked", allow(unused_mut))]
         let mut this = self.0.get_mut();
         let entries = &mut this.entries;
 
-        let idx = match query_idx(&entries, &new_entry.0) {
+        let slot = match route {
+            Route::Search => query_idx(entries, &new_entry.0),
+            Route::Path(path, slot) => {
+                checked::assume!(path.is_empty());
+                assert!(
+                    fits_slot(entries, slot, &new_entry.0),
+                    "The key doesn't belong to the location"
+                );
+                slot
+            }
+        };
+        let idx = match slot {
             Ok(idx) => {
                 let entry = mem::replace(&mut entries[idx], new_entry);
                 return (Some(entry), None);
@@ -123,35 +389,79 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Leaf<K, V, CAP> {
         };
 
         let next_next = this.next.take();
+        #[cfg_attr(feature = "unchecked", allow(dropping_references))]
         drop(this);
-        let this_weak = self.0.downgrade();
 
-        let mut next = Leaf(RcCell::new(LeafData {
-            entries: new_entries,
-            prev: Some(this_weak),
-            next: next_next,
-        }));
-        self.0.get_mut().next = Some(next.0.downgrade());
+        let mut next = spare.leaf(new_entries);
+        next.0.get_mut().next = next_next;
+        next.relink_next();
+        self.link_next(&mut next);
 
         (None, Some(next))
     }
 
-    fn remove<Q: Ord>(&mut self, query: &Q) -> Option<((K, V), bool)>
+    fn remove<Q: Ord>(&mut self, target: Target<'_, Q>) -> Option<((K, V), bool)>
     where
         K: Borrow<Q>,
     {
+        #[cfg_attr(feature = "unchecked", allow(unused_mut))]
         let mut this = self.0.get_mut();
         let entries = &mut this.entries;
-        let idx = query_idx(&entries, query).ok()?;
-        let b = CAP / 2 + 1;
+        let idx = match target {
+            Target::Key(query) => query_idx(entries, query).ok()?,
+            Target::First => 0,
+            Target::Last => entries.len().checked_sub(1)?,
+            Target::Nth(n) => n,
+        };
+        let b = CAP.div_ceil(2);
         Some((entries.remove(idx), entries.len() < b))
     }
 
+    fn occupancy(&self) -> usize {
+        Leaf::len(self)
+    }
+
+    fn redistribute(&mut self, next: &mut Self) -> bool {
+        #[cfg_attr(feature = "unchecked", allow(unused_mut))]
+        let mut this = self.0.get_mut();
+        #[cfg_attr(feature = "unchecked", allow(unused_mut))]
+        let mut next = next.0.get_mut();
+
+        if !redistribute(&mut this.entries, &mut next.entries) {
+            return false;
+        }
+
+        this.next = next.next.take();
+        #[cfg_attr(feature = "unchecked", allow(dropping_references))]
+        drop(this);
+        self.relink_next();
+        true
+    }
+
+    fn split_off_nth(&mut self, n: usize) -> Self {
+        #[cfg_attr(feature = "unchecked", allow(unused_mut))]
+        let mut this = self.0.get_mut();
+        let entries = this.entries.drain(n..).collect();
+        let next_next = this.next.take();
+        #[cfg_attr(feature = "unchecked", allow(dropping_references))]
+        drop(this);
+
+        let mut next = Leaf(RcCell::new(LeafData {
+            entries,
+            prev: None,
+            next: next_next,
+        }));
+        next.relink_next();
+        next
+    }
+
     fn balance_or_drain(&mut self, next: &mut Self, lacking_next: bool) -> bool {
+        #[cfg_attr(feature = "unchecked", allow(unused_mut))]
         let mut this = self.0.get_mut();
+        #[cfg_attr(feature = "unchecked", allow(unused_mut))]
         let mut next = next.0.get_mut();
 
-        let b = CAP / 2 + 1;
+        let b = CAP.div_ceil(2);
 
         if lacking_next && this.entries.len() > b {
             next.entries.insert(
@@ -170,29 +480,88 @@ impl<K: Ord, V, const CAP: usize> Node<K, V, CAP> for Leaf<K, V, CAP> {
 
         this.entries.extend(next.entries.drain(..));
         this.next = next.next.take();
+        #[cfg_attr(feature = "unchecked", allow(dropping_references))]
+        drop(this);
+        self.relink_next();
         true
     }
 }
 
-fn query_idx<K: Borrow<Q>, V, Q: Ord>(slice: &[(K, V)], query: &Q) -> Result<usize, usize> {
-    for (idx, (key, _)) in slice.iter().enumerate() {
-        return match key.borrow().cmp(query) {
-            Ordering::Greater => Err(idx),
-            Ordering::Equal => Ok(idx),
-            Ordering::Less => continue,
-        };
-    }
+pub(crate) fn query_idx<K: Borrow<Q>, V, Q: Ord>(
+    slice: &[(K, V)],
+    query: &Q,
+) -> Result<usize, usize> {
+    slice.binary_search_by(|(key, _)| key.borrow().cmp(query))
+}
 
-    Err(slice.len())
+fn fits_slot<K: Ord, V>(slice: &[(K, V)], slot: Result<usize, usize>, key: &K) -> bool {
+    match slot {
+        Ok(idx) => slice[idx].0 == *key,
+        Err(idx) => {
+            (idx == 0 || slice[idx - 1].0 < *key) && slice.get(idx).is_none_or(|e| *key < e.0)
+        }
+    }
 }
 
 impl<K: fmt::Debug, V: fmt::Debug, const CAP: usize> fmt::Debug for Leaf<K, V, CAP> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", "[")?;
+        write!(f, "[")?;
         for (k, _) in &self.0.get().entries {
             write!(f, " {:?}", k)?;
         }
-        write!(f, "{}", " ]")?;
+        write!(f, " ]")?;
         Ok(())
     }
 }
+
+#[test]
+fn query_idx_matches_linear_scan() {
+    use core::cmp::Ordering;
+
+    fn query_idx_linear<K: Ord, V>(slice: &[(K, V)], query: &K) -> Result<usize, usize> {
+        for (idx, (key, _)) in slice.iter().enumerate() {
+            return match key.cmp(query) {
+                Ordering::Greater => Err(idx),
+                Ordering::Equal => Ok(idx),
+                Ordering::Less => continue,
+            };
+        }
+
+        Err(slice.len())
+    }
+
+    for len in 0..=31 {
+        let entries: Vec<(u32, ())> = (0..len).map(|i| (i * 4 + 2, ())).collect();
+        for query in 0..len * 4 + 4 {
+            assert_eq!(
+                query_idx(&entries, &query),
+                query_idx_linear(&entries, &query),
+                "query {} in {} entries",
+                query,
+                len
+            );
+        }
+    }
+}
+
+#[test]
+fn relink_skips_dropped_next_leaf() {
+    let mut leaves: Vec<Leaf<u32, u32, 5>> = (0..3)
+        .map(|i| Leaf::from_entries([(i, i)].into_iter().collect()))
+        .collect();
+    link_leaves(&mut leaves);
+
+    let mut weak = leaves[2].0.downgrade();
+    assert!(weak.upgrade().is_some());
+    drop(leaves.pop());
+    assert!(weak.upgrade().is_none());
+
+    // The `next` link of the middle leaf is dangling now.
+    leaves[1].relink_next();
+    assert!(leaves[1].leaf_ref().next().is_none());
+    assert!(leaves[1]
+        .leaf_ref()
+        .prev()
+        .unwrap()
+        .ptr_eq(leaves[0].leaf_ref()));
+}
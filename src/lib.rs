@@ -1,45 +1,125 @@
-use std::cmp::Ord;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cmp::{Ord, Ordering};
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::{Bound, RangeBounds};
 
 use arrayvec::ArrayVec;
 
 #[cfg_attr(feature = "unchecked", path = "unchecked.rs")]
 mod checked;
+mod cursor;
+mod entry_api;
+mod error;
 mod internal;
+mod iter;
 mod leaf;
+mod location;
+mod map_by;
+mod merge;
 mod node;
+#[cfg(feature = "rayon")]
+mod par;
+pub mod prelude;
+mod rc;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod set;
+mod stats;
+#[cfg(test)]
+mod test_util;
 
 use checked::{Ref, RefKey, RefMut};
 use internal::Internal;
-use leaf::Leaf;
-use node::Node;
+use iter::{end_idx, start_idx};
+use leaf::{link_leaves, query_idx, rebalance_leaves, Leaf, LeafRef};
+use node::{Node, Route, Spare, Target};
+
+pub use cursor::{Cursor, CursorMut};
+pub use entry_api::{Entry, EntryMutResult, OccupiedEntry, OccupiedError, VacantEntry};
+pub use error::{AllocError, CapacityError, MAX_NODE_BYTES};
+pub use iter::{ExtractIf, IntoIter, Iter, IterMut, Keys, Range, RangeMut, Values, ValuesMut};
+pub use location::Location;
+pub use map_by::{BTreeMapBy, Comparator, EntryBy, Natural, Reverse, TotalOrder};
+pub use merge::{merge_join, MergeJoin, MergeSide};
+pub use set::{BTreeSet, Difference, Intersection, SymmetricDifference, Union};
+pub use stats::TreeStats;
 
-#[derive(Debug)]
 pub struct BTreeMap<K, V, const CAP: usize> {
     root: Option<Box<Internal<K, V, CAP>>>,
     length: usize,
 }
 
+/// Map with small nodes, which keeps shifting entries on insertion and removal cheap.
+pub type BTreeMap15<K, V> = BTreeMap<K, V, 15>;
+
+/// Map with large nodes, which makes lookups and iteration touch fewer nodes
+/// at the cost of moving more entries on each insertion and removal.
+pub type BTreeMap31<K, V> = BTreeMap<K, V, 31>;
+
+/// Map with the recommended node capacity.
+pub type DefaultBTreeMap<K, V> = BTreeMap15<K, V>;
+
 // The only reason this impls are not automatic is that
 // the type contains `RefCell<T>` and `Rc<T>`.
-// `RefCell`s are removed with the `"unchecked"` feature.
+// `RefCell`s are removed with the `"unchecked"` feature, which the `"std-compat"` enables.
 // All the `Rc<T>`s are local to each BTreeMap and not exposed,
 // and all the code which touches the reference count
 // requires to hold the `&mut BTreeMap<K, V>` reference.
-#[cfg(feature = "std-compat")]
-unsafe impl<K: Send, V: Send> Send for BTreeMap<K, V> {}
-#[cfg(feature = "std-compat")]
-unsafe impl<K: Sync, V: Sync> Sync for BTreeMap<K, V> {}
+#[cfg(all(feature = "std-compat", feature = "unchecked"))]
+unsafe impl<K: Send, V: Send, const CAP: usize> Send for BTreeMap<K, V, CAP> {}
+#[cfg(all(feature = "std-compat", feature = "unchecked"))]
+unsafe impl<K: Sync, V: Sync, const CAP: usize> Sync for BTreeMap<K, V, CAP> {}
 
 impl<K: Ord, V, const CAP: usize> BTreeMap<K, V, CAP> {
+    /// Creates an empty map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `CAP` is less than 4,
+    /// or `CAP` entries take more than `MAX_NODE_BYTES` bytes.
+    /// Use `try_new` to handle it without unwinding.
     #[inline]
     pub fn new() -> Self {
-        assert!(CAP % 2 == 1, "Node capacity must be an odd number");
-        assert!(CAP > 3, "Node capacity must be larger then 3");
+        match Self::try_new() {
+            Ok(map) => map,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Creates an empty map which is expected to hold about `len` entries.
+    ///
+    /// The nodes are allocated one by one as the entries are inserted,
+    /// so there's nothing to reserve in advance and it's the same as `new` for now.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same condition as the `new`.
+    #[inline]
+    pub fn with_expected_len(len: usize) -> Self {
+        let _ = len;
+        Self::new()
+    }
+
+    /// Creates an empty map, or returns which constraint the `CAP` violates.
+    #[inline]
+    pub fn try_new() -> Result<Self, CapacityError> {
+        if CAP < 4 {
+            return Err(CapacityError::TooSmall);
+        }
+        if CAP.saturating_mul(core::mem::size_of::<(K, V)>()) > MAX_NODE_BYTES {
+            return Err(CapacityError::TooLarge);
+        }
 
-        BTreeMap {
+        Ok(BTreeMap {
             root: None,
             length: 0,
-        }
+        })
     }
 
     #[inline]
@@ -52,151 +132,3207 @@ impl<K: Ord, V, const CAP: usize> BTreeMap<K, V, CAP> {
         self.length == 0
     }
 
+    /// Removes all the entries, dropping the whole tree.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.length = 0;
+    }
+
+    /// Maximum number of the entries in a leaf, or the children of an internal node.
+    #[inline]
+    pub const fn node_capacity() -> usize {
+        CAP
+    }
+
+    /// Minimum number of the entries in a leaf, or the children of an internal node,
+    /// except the root and its only leaf.
+    #[inline]
+    pub const fn min_entries() -> usize {
+        CAP.div_ceil(2)
+    }
+
+    /// Returns the number of levels above the leaves, or 0 if the map is empty.
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.root.as_ref().map_or(0, |root| root.height())
+    }
+
+    /// Returns the node counts and the occupancy of the tree, to help tuning the `CAP`.
+    ///
+    /// It traverses every internal node so it takes `O(n / CAP)` time.
+    pub fn stats(&self) -> TreeStats {
+        let (internal_node_count, leaf_count) =
+            self.root.as_ref().map_or((0, 0), |root| root.node_counts());
+        TreeStats::new(
+            self.height(),
+            internal_node_count,
+            leaf_count,
+            leaf_count * CAP,
+            self.length,
+        )
+    }
+
+    /// Panics if the tree is broken, for debugging the tree operations.
+    ///
+    /// Checks the occupancy and the depth of every node, the head and the tail leafs
+    /// and the stored length of every subtree, the order of the keys
+    /// and the `prev`/`next` links of the leafs.
+    ///
+    /// It takes `O(n)` time. Not a part of the stable API,
+    /// but public so the integration tests can call it in any build profile.
+    #[doc(hidden)]
+    pub fn check_invariants(&self) {
+        let b = Self::min_entries();
+        let root = match &self.root {
+            Some(root) => root,
+            None => return assert_eq!(self.length, 0),
+        };
+
+        let mut leaves = Vec::new();
+        root.check_invariants(true, &mut leaves);
+        assert_eq!(root.len(), self.length);
+
+        let mut length = 0;
+        for (idx, leaf) in leaves.iter().enumerate() {
+            let entries = leaf.entries();
+            assert!(entries.len() <= CAP && (leaves.len() == 1 || entries.len() >= b));
+            assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            match idx.checked_sub(1) {
+                Some(prev) => {
+                    assert!(leaves[prev].entries().last().unwrap().0 < entries[0].0);
+                    assert!(leaf.prev().unwrap().ptr_eq(leaves[prev]));
+                }
+                None => assert!(leaf.prev().is_none()),
+            }
+            match leaves.get(idx + 1) {
+                Some(next) => assert!(leaf.next().unwrap().ptr_eq(*next)),
+                None => assert!(leaf.next().is_none()),
+            }
+            length += entries.len();
+        }
+        assert_eq!(length, self.length);
+    }
+
+    /// Returns a `Debug` view of the tree structure, which lists the keys of each leaf.
+    pub fn debug_tree(&self) -> impl fmt::Debug + '_
+    where
+        K: fmt::Debug,
+        V: fmt::Debug,
+    {
+        &self.root
+    }
+
+    /// Returns the value of the `query`.
+    ///
+    /// With the `"unchecked"` feature, the `Ref` is a plain `&V` without any runtime guard.
     #[inline]
     pub fn get<Q>(&self, query: &Q) -> Option<Ref<'_, V>>
     where
-        K: std::borrow::Borrow<Q>,
+        K: core::borrow::Borrow<Q>,
         Q: Ord,
     {
         self.entry(query).map(|(_, v)| v)
     }
 
+    /// Returns a clone of the value of the `query`, which doesn't borrow the map.
     #[inline]
-    pub fn entry<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+    pub fn get_cloned<Q>(&self, query: &Q) -> Option<V>
     where
-        K: std::borrow::Borrow<Q>,
+        K: core::borrow::Borrow<Q>,
         Q: Ord,
+        V: Clone,
     {
-        let root = self.root.as_ref()?;
-        root.check_lower(query)?;
-        root.get(query)
+        self.entry(query).map(|(_, v)| V::clone(&v))
     }
 
+    /// Same as `get`, but returns a plain reference.
+    ///
+    /// Only available with the `"unchecked"` feature. The checked build can't hand out
+    /// a plain reference into a leaf without a guard, as it could outlive the runtime borrow check.
+    #[cfg(feature = "unchecked")]
     #[inline]
-    pub fn get_mut<Q>(&mut self, query: &Q) -> Option<RefMut<'_, V>>
+    pub fn get_ref<Q>(&self, query: &Q) -> Option<&V>
     where
-        K: std::borrow::Borrow<Q>,
+        K: core::borrow::Borrow<Q>,
         Q: Ord,
     {
-        self.entry_mut(query).map(|(_, v)| v)
+        self.get(query)
     }
 
+    /// Returns the number of the entries whose key is less than the `query`.
     #[inline]
-    pub fn entry_mut<Q>(&mut self, query: &Q) -> Option<(RefKey<'_, K>, RefMut<'_, V>)>
+    pub fn rank<Q>(&self, query: &Q) -> usize
     where
-        K: std::borrow::Borrow<Q>,
+        K: core::borrow::Borrow<Q>,
         Q: Ord,
     {
-        let root = self.root.as_mut()?;
-        root.check_lower(query)?;
-        root.get_mut(query)
+        self.root.as_ref().map_or(0, |root| root.rank(query))
+    }
+
+    /// Same as `select`, but borrows the value mutably. The `n` must be less than the `len`.
+    fn select_mut(&mut self, n: usize) -> (RefKey<'_, K>, RefMut<'_, V>) {
+        match self.root.as_mut() {
+            Some(root) if n < self.length => root.select_mut(n),
+            _ => checked::unreachable!("index should be less than the length"),
+        }
     }
 
+    /// Returns the `n`-th smallest entry, counting from zero.
     #[inline]
-    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        self.insert_entry(key, value).map(|(_, v)| v)
+    pub fn select(&self, n: usize) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        if n >= self.length {
+            return None;
+        }
+        Some(self.root.as_ref()?.select(n))
+    }
+
+    /// Returns the number of the leading entries whose key satisfies the `pred`,
+    /// descending the tree once instead of iterating the entries.
+    ///
+    /// The `pred` must return `true` for every key before the point and `false` after it,
+    /// same as the `slice::partition_point`. Otherwise the result is unspecified.
+    pub fn partition_point<F: FnMut(&K) -> bool>(&self, mut pred: F) -> usize {
+        self.root
+            .as_ref()
+            .map_or(0, |root| root.partition_point(&mut pred))
     }
 
+    /// Returns the number of the entries within the `range` without iterating them.
+    ///
+    /// Unlike the `range`, it returns 0 for the inverted range instead of panicking.
     #[inline]
-    pub fn insert_entry(&mut self, key: K, value: V) -> Option<(K, V)> {
-        let (prev, root) = match self.root.take() {
-            Some(mut node) => {
-                let (prev, new_node) = node.insert((key, value));
-                if let Some(new_node) = new_node {
-                    let parent = Internal::wrap(node, new_node);
-                    (prev, Box::new(parent))
-                } else {
-                    (prev, node)
-                }
-            }
-            None => (None, Box::new(Internal::new(Leaf::new((key, value))))),
-        };
-        self.root = Some(root);
+    pub fn count_range<Q, R>(&self, range: R) -> usize
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+        R: RangeBounds<Q>,
+    {
+        self.position_range(range).len()
+    }
 
-        if prev.is_none() {
-            self.length += 1;
+    /// Returns `true` if no key is within the `range`, without counting the entries.
+    ///
+    /// It descends the tree once for the start bound and compares the first key after it
+    /// with the end bound. Like the `count_range`, it returns `true` for the inverted range.
+    pub fn range_is_empty<Q, R>(&self, range: R) -> bool
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+        R: RangeBounds<Q>,
+    {
+        let first = match self.first_after(range.start_bound()) {
+            Some((key, _)) => key,
+            None => return true,
+        };
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+        let first = K::borrow(&first);
+        match range.end_bound() {
+            Bound::Included(query) => first > query,
+            Bound::Excluded(query) => first >= query,
+            Bound::Unbounded => false,
         }
+    }
 
-        prev
+    /// Returns the indices of the entries within the `range` in ascending key order,
+    /// which can be passed to the `nth`.
+    ///
+    /// Unlike the `range`, it returns an empty range for the inverted range instead of panicking.
+    pub fn position_range<Q, R>(&self, range: R) -> core::ops::Range<usize>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(query) => self.rank(query),
+            Bound::Excluded(query) => self.rank(query) + usize::from(self.contains_key(query)),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(query) => self.rank(query) + usize::from(self.contains_key(query)),
+            Bound::Excluded(query) => self.rank(query),
+            Bound::Unbounded => self.length,
+        };
+        start..end.max(start)
     }
 
+    /// Returns the first entry whose key is greater than or equal to the `query`.
     #[inline]
-    pub fn remove<Q>(&mut self, query: &Q) -> Option<V>
+    pub fn lower_bound<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
     where
-        K: std::borrow::Borrow<Q>,
+        K: core::borrow::Borrow<Q>,
         Q: Ord,
     {
-        self.remove_entry(query).map(|(_, v)| v)
+        self.first_after(Bound::Included(query))
     }
 
+    /// Returns the first entry whose key is greater than the `query`.
     #[inline]
-    pub fn remove_entry<Q>(&mut self, query: &Q) -> Option<(K, V)>
+    pub fn upper_bound<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
     where
-        K: std::borrow::Borrow<Q>,
+        K: core::borrow::Borrow<Q>,
         Q: Ord,
     {
-        let root = self.root.as_mut()?;
-        root.check_lower(query)?;
-        let (entry, need_merge) = root.remove(query)?;
+        self.first_after(Bound::Excluded(query))
+    }
 
-        self.length -= 1;
+    /// Returns the entry with the largest key less than or equal to the `query`.
+    #[inline]
+    pub fn floor<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.last_before(Bound::Included(query))
+    }
 
-        if need_merge {
-            if self.length == 0 {
-                self.root = None;
-            } else if let Some(node) = root.pop_depth() {
-                self.root = Some(node)
+    /// Returns the entry with the smallest key greater than or equal to the `query`.
+    ///
+    /// Same as `lower_bound`.
+    #[inline]
+    pub fn ceil<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.lower_bound(query)
+    }
+
+    /// Returns the entry with the largest key less than the `query`.
+    #[inline]
+    pub fn predecessor<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.last_before(Bound::Excluded(query))
+    }
+
+    /// Returns the entry with the smallest key greater than the `query`.
+    ///
+    /// Same as `upper_bound`.
+    #[inline]
+    pub fn successor<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.upper_bound(query)
+    }
+
+    /// Returns the first entry after the start `bound`.
+    fn first_after<Q>(&self, bound: Bound<&Q>) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let (leaf, idx) = self.position_after(bound)?;
+        Some(leaf.entry(idx))
+    }
+
+    /// Returns the last entry before the end `bound`.
+    fn last_before<Q>(&self, bound: Bound<&Q>) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let (leaf, idx) = self.position_before(bound)?;
+        Some(leaf.entry(idx))
+    }
+
+    /// Returns the leaf and the index of the first entry after the start `bound`.
+    fn position_after<Q>(&self, bound: Bound<&Q>) -> Option<(LeafRef<'_, K, V, CAP>, usize)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let root = self.root.as_ref()?;
+        let leaf = match bound {
+            Bound::Included(query) | Bound::Excluded(query) => root.leaf_for(query).leaf_ref(),
+            Bound::Unbounded => root.head().leaf_ref(),
+        };
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+        let idx = start_idx(&leaf.entries(), bound);
+        let len = leaf.entries().len();
+
+        // The located leaf may be entirely before the bound, then it's the head of the next one.
+        match idx < len {
+            true => Some((leaf, idx)),
+            false => Some((leaf.next()?, 0)),
+        }
+    }
+
+    /// Returns the leaf and the index of the last entry before the end `bound`.
+    fn position_before<Q>(&self, bound: Bound<&Q>) -> Option<(LeafRef<'_, K, V, CAP>, usize)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let root = self.root.as_ref()?;
+        let leaf = match bound {
+            Bound::Included(query) | Bound::Excluded(query) => root.leaf_for(query).leaf_ref(),
+            Bound::Unbounded => root.tail().leaf_ref(),
+        };
+        #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+        let idx = end_idx(&leaf.entries(), bound);
+
+        // The located leaf may be entirely after the bound, then it's the tail of the previous one.
+        match idx.checked_sub(1) {
+            Some(idx) => Some((leaf, idx)),
+            None => {
+                let prev = leaf.prev()?;
+                let len = prev.entries().len();
+                Some((prev, len - 1))
             }
         }
+    }
 
-        Some(entry)
+    /// Same as `select`, named after the `Iterator::nth` for paged access.
+    ///
+    /// Unlike the `iter().nth(n)`, it descends the tree in `O(log n)`.
+    #[inline]
+    pub fn nth(&self, n: usize) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        self.select(n)
     }
-}
 
-fn insert_or_split<T, const CAP: usize>(
-    buf: &mut ArrayVec<T, CAP>,
-    idx: usize,
-    new: T,
-) -> Option<ArrayVec<T, CAP>> {
-    if !buf.is_full() {
-        buf.insert(idx, new);
-        return None;
+    /// Returns the key and the value of the `query`.
+    ///
+    /// Note that the `entry` method of this map is the same as this method,
+    /// unlike the `std::collections::BTreeMap::entry` which returns the Entry API.
+    #[inline]
+    pub fn get_key_value<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.entry(query)
     }
 
-    let mut new_buf = ArrayVec::new();
-    let b = CAP / 2 + 1;
+    /// Same as `get_key_value`.
+    #[inline]
+    pub fn entry<Q>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let root = self.root.as_ref()?;
+        root.check_lower(query)?;
+        root.get(query)
+    }
 
-    if idx < b {
-        new_buf.extend(buf.drain(b - 1..));
-        buf.insert(idx, new);
-    } else {
-        new_buf.extend(buf.drain(b..));
-        new_buf.insert(idx - b, new);
+    /// Returns `true` if the map contains the `query`.
+    #[inline]
+    pub fn contains_key<Q>(&self, query: &Q) -> bool
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.entry(query).is_some()
     }
 
-    Some(new_buf)
-}
+    /// Returns the entry with the smallest key.
+    #[inline]
+    pub fn first_key_value(&self) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        Some(self.root.as_ref()?.head().first_entry())
+    }
 
-#[test]
-fn check_same_behavior_with_std_btreemap() {
-    let mut m1 = std::collections::BTreeMap::new();
-    let mut m2 = BTreeMap::<_, _, 15>::new();
+    /// Returns the entry with the largest key.
+    #[inline]
+    pub fn last_key_value(&self) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        Some(self.root.as_ref()?.tail().last_entry())
+    }
 
-    let nums: Vec<u32> = std::iter::repeat_with(rand::random)
-        .take(1024 * 1024)
-        .collect();
+    /// Returns a clone of the smallest key, which doesn't borrow the map.
+    #[inline]
+    pub fn peek_first_key(&self) -> Option<K>
+    where
+        K: Clone,
+    {
+        Some(self.root.as_ref()?.head().first().clone())
+    }
 
-    for &n in &nums {
-        assert_eq!(m1.insert(n, n), m2.insert(n, n));
+    /// Returns a clone of the largest key, which doesn't borrow the map.
+    #[inline]
+    pub fn peek_last_key(&self) -> Option<K>
+    where
+        K: Clone,
+    {
+        Some(self.root.as_ref()?.tail().last().clone())
     }
-    for &n in &nums {
-        assert_eq!(m1.get(&n), m2.get(&n).as_deref());
-        assert_eq!(
-            m1.get(&n.wrapping_add(1)),
-            m2.get(&n.wrapping_add(1)).as_deref()
-        );
+
+    #[inline]
+    pub fn get_mut<Q>(&mut self, query: &Q) -> Option<RefMut<'_, V>>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.entry_mut(query).map(|(_, v)| v)
     }
-    for &n in &nums {
-        assert_eq!(m1.remove(&n), m2.remove(&n));
+
+    #[inline]
+    pub fn entry_mut<Q>(&mut self, query: &Q) -> Option<(RefKey<'_, K>, RefMut<'_, V>)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let root = self.root.as_mut()?;
+        root.check_lower(query)?;
+        root.get_mut(query)
+    }
+
+    /// Same as `entry_mut`, but returns the vacant entry on a miss,
+    /// which inserts at the located position without descending again.
+    #[inline]
+    pub fn entry_or_vacant_mut(&mut self, key: K) -> EntryMutResult<'_, K, V, CAP> {
+        let location = self.locate(&key);
+        match location.is_occupied() {
+            true => match location.into_entry_mut() {
+                Some(entry) => Ok(entry),
+                None => checked::unreachable!("location should be occupied"),
+            },
+            false => Err(VacantEntry::new(key, location)),
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V, CAP> {
+        Iter::new(self)
+    }
+
+    /// Iterates over the entries whose keys are greater than or equal to the `start`.
+    ///
+    /// Same as the `range(start..)`, but the iterator knows its exact length.
+    #[inline]
+    pub fn iter_from<Q>(&self, start: &Q) -> Iter<'_, K, V, CAP>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        Iter::starting_at(self, start)
+    }
+
+    /// Iterates mutably over the entries in ascending key order.
+    /// Keys can't be modified to keep the order.
+    #[inline]
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V, CAP> {
+        IterMut::new(self)
+    }
+
+    #[inline]
+    pub fn keys(&self) -> Keys<'_, K, V, CAP> {
+        Keys::new(self.iter())
+    }
+
+    #[inline]
+    pub fn values(&self) -> Values<'_, K, V, CAP> {
+        Values::new(self.iter())
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V, CAP> {
+        ValuesMut::new(self.iter_mut())
+    }
+
+    /// Same as `iter`, but also yields the position of each entry in the sorted order.
+    #[inline]
+    pub fn iter_ranked(&self) -> impl Iterator<Item = (usize, Ref<'_, K>, Ref<'_, V>)> {
+        self.iter()
+            .enumerate()
+            .map(|(rank, (key, value))| (rank, key, value))
+    }
+
+    /// Iterates over the entries whose keys are within the `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the range is greater than its end,
+    /// or if both ends are equal and excluded.
+    #[inline]
+    pub fn range<Q: Ord, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, K, V, CAP>
+    where
+        K: core::borrow::Borrow<Q>,
+    {
+        Range::new(self, range)
+    }
+
+    /// Iterates mutably over the entries whose keys are within the `range`.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same condition as `range`.
+    #[inline]
+    pub fn range_mut<Q: Ord, R: RangeBounds<Q>>(&mut self, range: R) -> RangeMut<'_, K, V, CAP>
+    where
+        K: core::borrow::Borrow<Q>,
+    {
+        RangeMut::new(self, range)
+    }
+
+    /// Borrows the values of the `N` keys at once, with `None` for the missing keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any two keys are equal.
+    pub fn get_disjoint_mut<Q, const N: usize>(
+        &mut self,
+        keys: [&Q; N],
+    ) -> [Option<RefMut<'_, V>>; N]
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let mut values = core::array::from_fn(|_| None);
+        self.fill_disjoint_mut(&keys, &mut values, "get_disjoint_mut");
+        values
+    }
+
+    /// Swaps the values of the keys `a` and `b` in place.
+    /// Returns `false` without modifying the map if either key is missing.
+    pub fn swap_values<Q>(&mut self, a: &Q, b: &Q) -> bool
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        if a == b {
+            return self.contains_key(a);
+        }
+
+        match self.get_disjoint_mut([a, b]) {
+            #[cfg_attr(feature = "unchecked", allow(unused_mut))]
+            [Some(mut a), Some(mut b)] => {
+                core::mem::swap(&mut *a, &mut *b);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Same as `get_disjoint_mut`, but takes any number of keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any two keys are equal.
+    pub fn get_many_mut<Q>(&mut self, keys: &[&Q]) -> Vec<Option<RefMut<'_, V>>>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let mut values: Vec<_> = keys.iter().map(|_| None).collect();
+        self.fill_disjoint_mut(keys, &mut values, "get_many_mut");
+        values
+    }
+
+    fn fill_disjoint_mut<'a, Q>(
+        &'a mut self,
+        keys: &[&Q],
+        values: &mut [Option<RefMut<'a, V>>],
+        method: &str,
+    ) where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_unstable_by(|&lhs, &rhs| keys[lhs].cmp(keys[rhs]));
+        assert!(
+            order.windows(2).all(|pair| keys[pair[0]] != keys[pair[1]]),
+            "duplicate keys in {}",
+            method
+        );
+
+        let this: &'a Self = self;
+        let root = match &this.root {
+            Some(root) => root,
+            None => return,
+        };
+        let positions: Vec<Option<(LeafRef<'a, K, V, CAP>, usize)>> = keys
+            .iter()
+            .map(|&key| {
+                let leaf = root.leaf_for(key).leaf_ref();
+                #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+                let pos = query_idx(&leaf.entries(), key).ok()?;
+                Some((leaf, pos))
+            })
+            .collect();
+
+        // Each leaf is borrowed once, and split in the ascending key order.
+        let mut current: Option<(LeafRef<'a, K, V, CAP>, _, usize)> = None;
+        for idx in order {
+            let (leaf, pos) = match positions[idx] {
+                Some(position) => position,
+                None => continue,
+            };
+            let (rest, offset) = match current.take() {
+                Some((prev, rest, offset)) if prev.ptr_eq(leaf) => (rest, offset),
+                // SAFETY: The map is exclusively borrowed for `'a`, and the keys are distinct
+                // and visited in ascending order, so each leaf is borrowed once and split
+                // into the disjoint entries.
+                _ => (unsafe { leaf.entries_mut() }, 0),
+            };
+            let (entry, rest) =
+                checked::split_mut(rest, |rest| match rest[pos - offset..].split_first_mut() {
+                    Some(split) => split,
+                    None => checked::unreachable!("located entry should exist"),
+                });
+            values[idx] = Some(checked::map_mut(entry, |entry| &mut entry.1));
+            current = Some((leaf, rest, pos + 1));
+        }
+    }
+
+    /// Removes and yields the entries for which the `pred` returns `true`, in ascending key order.
+    ///
+    /// Entries are removed one by one while iterating, so dropping the iterator early
+    /// keeps the rest of the entries in the map.
+    #[inline]
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, CAP, F>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        ExtractIf::new(self, pred)
+    }
+
+    /// Borrows the first up to `N` entries within the `range` at once.
+    /// Slots after the last entry of the range are filled with `None`.
+    pub fn range_mut_array<Q: Ord, R: RangeBounds<Q>, const N: usize>(
+        &mut self,
+        range: R,
+    ) -> [Option<(RefKey<'_, K>, RefMut<'_, V>)>; N]
+    where
+        K: core::borrow::Borrow<Q>,
+    {
+        let mut iter = self.range_mut(range);
+        core::array::from_fn(|_| iter.next())
+    }
+
+    /// Walks both maps in ascending key order at once, yielding every distinct key
+    /// with the map(s) it is contained in.
+    #[inline]
+    pub fn merge_join<'a, W>(
+        &'a self,
+        other: &'a BTreeMap<K, W, CAP>,
+    ) -> MergeJoin<'a, K, V, W, CAP> {
+        MergeJoin::new(self.iter(), other.iter())
+    }
+
+    #[inline]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_entry(key, value).map(|(_, v)| v)
+    }
+
+    #[inline]
+    pub fn insert_entry(&mut self, key: K, value: V) -> Option<(K, V)> {
+        self.insert_routed((key, value), Route::Search)
+    }
+
+    /// Same as the `insert_entry`, but returns `AllocError` instead of aborting
+    /// if the nodes created by splitting a full leaf can't be allocated.
+    /// The map is left untouched on error.
+    ///
+    /// The nodes the insertion needs are allocated before modifying the tree.
+    /// Inserting into a leaf with a spare slot or replacing a value never allocates.
+    pub fn try_insert_entry(&mut self, key: K, value: V) -> Result<Option<(K, V)>, AllocError> {
+        let (leaf, internals) = match &self.root {
+            Some(root) => {
+                let (splits, levels) = root.split_depth(&key);
+                // Every split internal node adds a sibling, and splitting the root adds a new root too.
+                (
+                    splits > 0,
+                    splits.saturating_sub(1) + usize::from(splits == levels),
+                )
+            }
+            None => (true, 1),
+        };
+        let mut spare = Spare::reserve(leaf, internals)?;
+
+        let prev = self.insert_spared((key, value), Route::Search, &mut spare);
+        debug_assert!(
+            spare.is_empty(),
+            "reserved more nodes than the insertion needs"
+        );
+        Ok(prev)
+    }
+
+    /// Inserts the entry if the `key` doesn't exist and returns the inserted value.
+    /// Otherwise returns the existing entry and the given `value` without modifying the map.
+    #[inline]
+    pub fn try_insert(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<RefMut<'_, V>, OccupiedError<'_, K, V, CAP>> {
+        match self.entry_at(key) {
+            Entry::Occupied(entry) => Err(OccupiedError { entry, value }),
+            Entry::Vacant(entry) => Ok(entry.insert(value)),
+        }
+    }
+
+    /// Returns the value of the `key`, inserting the result of `f` if the key doesn't exist.
+    ///
+    /// Shortcut of the `entry_at(key).or_insert_with(f)`, which descends the tree only once.
+    #[inline]
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, f: F) -> RefMut<'_, V> {
+        self.entry_at(key).or_insert_with(f)
+    }
+
+    /// Returns the value of the `key`, inserting the default value if the key doesn't exist.
+    ///
+    /// Shortcut of the `entry_at(key).or_default()`, which descends the tree only once.
+    #[inline]
+    pub fn get_or_insert_default(&mut self, key: K) -> RefMut<'_, V>
+    where
+        V: Default,
+    {
+        self.entry_at(key).or_default()
+    }
+
+    /// Finds the position of the `query` with a single descent.
+    /// The returned `Location` can read the entry or insert a new one there
+    /// without descending again.
+    #[inline]
+    pub fn locate<Q>(&mut self, query: &Q) -> Location<'_, K, V, CAP>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let mut path = Vec::new();
+        let slot = match &self.root {
+            Some(root) => root.locate(query, &mut path),
+            None => Err(0),
+        };
+        Location::new(self, path, slot)
+    }
+
+    /// Gets the entry of the `key` for in-place manipulation.
+    ///
+    /// Unlike the `std`, `entry` of this map is a lookup same as `get_key_value`.
+    #[inline]
+    pub fn entry_at(&mut self, key: K) -> Entry<'_, K, V, CAP> {
+        Entry::new(self, key)
+    }
+
+    /// Removes all but the `n` largest entries.
+    ///
+    /// The tree is cut at the `n`-th largest entry and the nodes before it are dropped as a whole,
+    /// so it takes `O(log n)` time besides dropping the removed entries.
+    pub fn keep_last_n(&mut self, n: usize) {
+        if let Some(excess) = self.length.checked_sub(n) {
+            *self = self.split_at_nth(excess);
+        }
+    }
+
+    /// Removes all but the `n` smallest entries.
+    ///
+    /// Like `keep_last_n`, it only rebalances the nodes along the cut.
+    pub fn keep_first_n(&mut self, n: usize) {
+        drop(self.split_at_nth(n));
+    }
+
+    /// Calls `f` with every entry in ascending key order, following the leaf chain
+    /// without the per entry guards of the `iter_mut`.
+    pub fn for_each_mut<F: FnMut(&K, &mut V)>(&mut self, mut f: F) {
+        let mut leaf = self.root.as_ref().map(|root| root.head().leaf_ref());
+        while let Some(current) = leaf {
+            // SAFETY: The map is exclusively borrowed, so is every leaf,
+            // and the guard of the current leaf is dropped before moving to the next one.
+            for (key, value) in unsafe { current.entries_mut() }.iter_mut() {
+                f(key, value);
+            }
+            leaf = current.next();
+        }
+    }
+
+    /// Removes every entry for which `f` returns `false`, visiting them in ascending key order.
+    ///
+    /// The entries are filtered within each leaf and the tree is rebalanced once at the end.
+    /// If `f` panics, the map still has the entries kept so far and the ones not visited yet,
+    /// including the one `f` panicked on.
+    #[inline]
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        // `ArrayVec::retain` keeps the unvisited entries if `f` panics.
+        self.retain_leaves(|entries| entries.retain(|(key, value)| f(key, value)))
+    }
+
+    /// Calls `f` with every entry in ascending key order, keeping the entry with the returned value
+    /// or removing it if `f` returns `None`.
+    ///
+    /// If `f` panics, the map still has the entries kept so far and the ones not visited yet.
+    /// The entry `f` panicked on is dropped, as it was moved into the `f`.
+    pub fn retain_map<F: FnMut(&K, V) -> Option<V>>(&mut self, mut f: F) {
+        /// Puts the unvisited entries back after the kept ones, even if the `f` panics.
+        struct Unvisited<'a, T, const CAP: usize> {
+            kept: &'a mut ArrayVec<T, CAP>,
+            rest: arrayvec::IntoIter<T, CAP>,
+        }
+
+        impl<T, const CAP: usize> Drop for Unvisited<'_, T, CAP> {
+            fn drop(&mut self) {
+                self.kept.extend(self.rest.by_ref());
+            }
+        }
+
+        self.retain_leaves(|entries| {
+            let rest = core::mem::take(entries).into_iter();
+            let mut entries = Unvisited {
+                kept: entries,
+                rest,
+            };
+            for (key, value) in entries.rest.by_ref() {
+                if let Some(value) = f(&key, value) {
+                    entries.kept.push((key, value));
+                }
+            }
+        })
+    }
+
+    /// Calls `filter` with the entries of every leaf in order,
+    /// then rebuilds the tree over the filtered leafs, even if the `filter` panics.
+    fn retain_leaves(&mut self, mut filter: impl FnMut(&mut ArrayVec<(K, V), CAP>)) {
+        struct Rebuild<'a, K: Ord, V, const CAP: usize> {
+            map: &'a mut BTreeMap<K, V, CAP>,
+            leaves: Vec<Leaf<K, V, CAP>>,
+        }
+
+        impl<K: Ord, V, const CAP: usize> Drop for Rebuild<'_, K, V, CAP> {
+            fn drop(&mut self) {
+                let leaves = core::mem::take(&mut self.leaves);
+                self.map.rebuild(leaves);
+            }
+        }
+
+        let leaves = self.take_leaves();
+        let mut guard = Rebuild { map: self, leaves };
+        for leaf in &mut guard.leaves {
+            #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+            filter(&mut leaf.entries_mut());
+        }
+    }
+
+    /// Returns a new map with the same keys and the values transformed by the `f`.
+    ///
+    /// The leafs are copied one by one following the chain, so it takes `O(n)` time
+    /// and the new map has the same leafs as the `self`.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same condition as the `new` for the value type `W`.
+    pub fn map_values<W, F: FnMut(&V) -> W>(&self, mut f: F) -> BTreeMap<K, W, CAP>
+    where
+        K: Clone,
+    {
+        let mut map = BTreeMap::new();
+        let mut leaves = Vec::new();
+        let mut leaf = self.root.as_ref().map(|root| root.head().leaf_ref());
+        while let Some(current) = leaf {
+            let entries = current.entries();
+            let mapped = entries.iter().map(|(key, value)| (key.clone(), f(value)));
+            leaves.push(Leaf::from_entries(mapped.collect()));
+            leaf = current.next();
+        }
+        link_leaves(&mut leaves);
+
+        map.build(leaves);
+        map
+    }
+
+    /// Consumes the map and returns its entries in ascending key order.
+    #[inline]
+    pub fn into_sorted_vec(self) -> Vec<(K, V)> {
+        self.into_iter().collect()
+    }
+
+    /// Removes the entries whose keys are within the `range` and returns the number of them.
+    ///
+    /// Only the nodes along the paths to both ends of the range are rebalanced,
+    /// so it takes `O(log n)` time besides dropping the removed entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same condition as `range`.
+    pub fn remove_range<Q: Ord, R: RangeBounds<Q>>(&mut self, range: R) -> usize
+    where
+        K: core::borrow::Borrow<Q>,
+    {
+        iter::check_range(&range);
+
+        let span = self.position_range(range);
+        if span.is_empty() {
+            return 0;
+        }
+
+        // Cut the tree at both ends of the range and join the outer parts back,
+        // which drops the nodes in between as a whole.
+        let tail = self.split_at_nth(span.end);
+        drop(self.split_at_nth(span.start));
+        self.concat(tail);
+
+        span.len()
+    }
+
+    /// Splits the map at the `key`, returning the entries with the keys greater than or equal to it.
+    ///
+    /// Same as the `split_at_nth` at the rank of the `key`, so it takes `O(log n)` time.
+    pub fn split_off<Q: Ord>(&mut self, key: &Q) -> Self
+    where
+        K: core::borrow::Borrow<Q>,
+    {
+        let n = self.rank(key);
+        self.split_at_nth(n)
+    }
+
+    /// Splits the map at the index `n`, keeping the first `n` entries in the `self`
+    /// and returning the rest. Returns an empty map if `n` is not less than the length.
+    ///
+    /// Only the nodes along the path to the `n`-th entry are split and rebalanced,
+    /// so it takes `O(log n)` time.
+    pub fn split_at_nth(&mut self, n: usize) -> Self {
+        let mut other = BTreeMap {
+            root: None,
+            length: 0,
+        };
+        if n >= self.length {
+            return other;
+        }
+        if n == 0 {
+            core::mem::swap(self, &mut other);
+            return other;
+        }
+
+        let root = self
+            .root
+            .as_mut()
+            .unwrap_or_else(|| checked::unreachable!("non-empty map should have the root"));
+        other.root = Some(Internal::split_nth(root, n));
+        other.length = self.length - n;
+        self.length = n;
+        other
+    }
+
+    /// Moves all the entries of the `other` into the `self`, leaving the `other` empty.
+    /// On key collision, the entry of the `other` replaces the one of the `self`.
+    ///
+    /// If all the keys of the `other` are greater than the keys of the `self`,
+    /// the lower tree is grafted onto the border of the other one in `O(log n)` time.
+    /// Otherwise both maps are merged entry by entry.
+    pub fn append(&mut self, other: &mut Self) {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            core::mem::swap(self, other);
+            return;
+        }
+
+        let disjoint = match (&self.root, &other.root) {
+            (Some(left), Some(right)) => *left.tail().last() < *right.head().first(),
+            _ => false,
+        };
+
+        if disjoint {
+            let right = core::mem::replace(
+                other,
+                BTreeMap {
+                    root: None,
+                    length: 0,
+                },
+            );
+            self.concat(right);
+            return;
+        }
+
+        let (left_len, right_len) = (self.length, other.length);
+        let mut left = IntoIter::new(self.take_leaves(), left_len).peekable();
+        let mut right = IntoIter::new(other.take_leaves(), right_len).peekable();
+
+        let mut entries = Vec::with_capacity(left_len + right_len);
+        loop {
+            let entry = match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) => match l.0.cmp(&r.0) {
+                    core::cmp::Ordering::Less => left.next(),
+                    core::cmp::Ordering::Greater => right.next(),
+                    core::cmp::Ordering::Equal => {
+                        left.next();
+                        right.next()
+                    }
+                },
+                (Some(_), None) => left.next(),
+                (None, _) => right.next(),
+            };
+            match entry {
+                Some(entry) => entries.push(entry),
+                None => break,
+            }
+        }
+
+        self.load_sorted(entries);
+    }
+
+    /// Rebuilds the tree with the leafs filled as full as possible,
+    /// which makes it shallower and use less memory after many removals.
+    ///
+    /// It moves every entry once so it takes `O(n)` time.
+    pub fn compact(&mut self) {
+        let len = self.length;
+        let entries: Vec<_> = IntoIter::new(self.take_leaves(), len).collect();
+        self.load_sorted(entries);
+    }
+
+    /// Appends the entries of the `other`, whose keys must be all greater than the keys of the `self`.
+    fn concat(&mut self, other: Self) {
+        let BTreeMap { root, length } = other;
+        self.root = match (self.root.take(), root) {
+            (Some(left), Some(right)) => Some(Internal::join(left, right)),
+            (left, right) => left.or(right),
+        };
+        self.length += length;
+    }
+
+    /// Dismantles the tree into its leafs, leaving the map empty.
+    fn take_leaves(&mut self) -> Vec<Leaf<K, V, CAP>> {
+        let mut leaves = Vec::new();
+        if let Some(root) = self.root.take() {
+            root.into_leaves(&mut leaves);
+        }
+        self.length = 0;
+        leaves
+    }
+
+    /// Replaces the content of the map with the tree built over the entries
+    /// sorted by the key without duplicates, filling the leafs as full as possible.
+    fn load_sorted(&mut self, entries: Vec<(K, V)>) {
+        let leaves = distribute::<_, CAP>(entries)
+            .into_iter()
+            .map(Leaf::from_entries)
+            .collect();
+        self.rebuild(leaves);
+    }
+
+    /// Replaces the content of the map with the tree built over the sorted leafs.
+    fn rebuild(&mut self, leaves: Vec<Leaf<K, V, CAP>>) {
+        self.build(rebalance_leaves(leaves));
+    }
+
+    /// Same as `rebuild`, but the leafs must be already balanced and linked.
+    fn build(&mut self, leaves: Vec<Leaf<K, V, CAP>>) {
+        self.length = leaves.iter().map(Leaf::len).sum();
+        self.root = match leaves.is_empty() {
+            true => None,
+            false => Some(Internal::build(leaves)),
+        };
+    }
+
+    fn insert_routed(&mut self, new_entry: (K, V), route: Route<'_>) -> Option<(K, V)> {
+        self.insert_spared(new_entry, route, &mut Spare::new())
+    }
+
+    /// Same as the `insert_routed`, but takes the nodes created by the splits from the `spare`.
+    fn insert_spared(
+        &mut self,
+        new_entry: (K, V),
+        route: Route<'_>,
+        spare: &mut Spare<K, V, CAP>,
+    ) -> Option<(K, V)> {
+        let (prev, root) = match self.root.take() {
+            Some(mut node) => {
+                let (prev, new_node) = node.insert(new_entry, route, spare);
+                if let Some(new_node) = new_node {
+                    let parent = Internal::wrap(node, new_node);
+                    (prev, spare.internal(parent))
+                } else {
+                    (prev, node)
+                }
+            }
+            None => {
+                let leaf = spare.leaf([new_entry].into_iter().collect());
+                (None, spare.internal(Internal::new(leaf)))
+            }
+        };
+        self.root = Some(root);
+
+        if prev.is_none() {
+            self.length = self
+                .length
+                .checked_add(1)
+                .unwrap_or_else(|| checked::unreachable!("length shouldn't overflow"));
+        }
+
+        prev
+    }
+
+    #[inline]
+    pub fn remove<Q>(&mut self, query: &Q) -> Option<V>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.remove_entry(query).map(|(_, v)| v)
+    }
+
+    #[inline]
+    pub fn remove_entry<Q>(&mut self, query: &Q) -> Option<(K, V)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.remove_with_info(query).map(|(k, v, _)| (k, v))
+    }
+
+    /// Same as `remove_entry`, but also reports whether the removal
+    /// decreased the `height()` of the tree.
+    #[inline]
+    pub fn remove_with_info<Q>(&mut self, query: &Q) -> Option<(K, V, bool)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        self.root.as_ref()?.check_lower(query)?;
+        self.remove_target(Target::Key(query))
+    }
+
+    /// Returns the entry with the smallest key for in-place manipulation.
+    #[inline]
+    pub fn first_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, CAP>> {
+        self.nth_entry(0)
+    }
+
+    /// Returns the entry with the largest key for in-place manipulation.
+    #[inline]
+    pub fn last_entry(&mut self) -> Option<OccupiedEntry<'_, K, V, CAP>> {
+        let n = self.length.checked_sub(1)?;
+        self.nth_entry(n)
+    }
+
+    fn nth_entry(&mut self, n: usize) -> Option<OccupiedEntry<'_, K, V, CAP>> {
+        let mut path = Vec::new();
+        let idx = self.root.as_ref()?.locate_nth(n, &mut path);
+        Some(OccupiedEntry::new(Location::new(self, path, Ok(idx))))
+    }
+
+    /// Removes and returns the entry with the smallest key.
+    #[inline]
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        let (key, value, _) = self.remove_target(Target::<K>::First)?;
+        Some((key, value))
+    }
+
+    /// Removes and returns the entry with the largest key.
+    #[inline]
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        let (key, value, _) = self.remove_target(Target::<K>::Last)?;
+        Some((key, value))
+    }
+
+    fn remove_target<Q>(&mut self, target: Target<'_, Q>) -> Option<(K, V, bool)>
+    where
+        K: core::borrow::Borrow<Q>,
+        Q: Ord,
+    {
+        let root = self.root.as_mut()?;
+        let ((key, value), need_merge) = root.remove(target)?;
+
+        self.length = self
+            .length
+            .checked_sub(1)
+            .unwrap_or_else(|| checked::unreachable!("removed entry should be counted"));
+
+        let mut shrunk = false;
+        if need_merge {
+            if self.length == 0 {
+                self.root = None;
+                shrunk = true;
+            } else if let Some(node) = root.pop_depth() {
+                self.root = Some(node);
+                shrunk = true;
+            }
+        }
+
+        Some((key, value, shrunk))
+    }
+}
+
+/// Same as `BTreeMap::new`.
+///
+/// # Panics
+///
+/// Panics on the same condition as the `new`.
+impl<K: Ord, V, const CAP: usize> Default for BTreeMap<K, V, CAP> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V: Clone, const CAP: usize> Clone for BTreeMap<K, V, CAP> {
+    /// Copies the leafs in bulk following the chain and builds the spine over them.
+    fn clone(&self) -> Self {
+        let mut leaves = Vec::new();
+        let mut leaf = self.root.as_ref().map(|root| root.head().leaf_ref());
+        while let Some(current) = leaf {
+            leaves.push(current.deep_clone());
+            leaf = current.next();
+        }
+        link_leaves(&mut leaves);
+
+        let mut map = BTreeMap {
+            root: None,
+            length: 0,
+        };
+        map.build(leaves);
+        map
+    }
+
+    /// Copies the entries into the leafs of the `self` where possible,
+    /// allocating only the leafs which the `self` lacks and the spine.
+    fn clone_from(&mut self, source: &Self) {
+        let mut spare = self.take_leaves().into_iter();
+        let mut leaves = Vec::new();
+        let mut leaf = source.root.as_ref().map(|root| root.head().leaf_ref());
+        while let Some(current) = leaf {
+            match spare.next() {
+                Some(mut reused) => {
+                    #[cfg_attr(feature = "unchecked", allow(unused_mut))]
+                    let (mut entries, from) = (reused.entries_mut(), current.entries());
+                    entries.truncate(from.len());
+                    let (head, tail) = from.split_at(entries.len());
+                    entries.clone_from_slice(head);
+                    entries.extend(tail.iter().cloned());
+                    #[cfg_attr(feature = "unchecked", allow(dropping_references))]
+                    drop(entries);
+                    leaves.push(reused);
+                }
+                None => leaves.push(current.deep_clone()),
+            }
+            leaf = current.next();
+        }
+        drop(spare);
+        link_leaves(&mut leaves);
+
+        self.build(leaves);
+    }
+}
+
+/// The `CAP` can't be inferred from the iterator, so it should be annotated on the collected type.
+/// On duplicate keys, the later entry overwrites the earlier one.
+///
+/// While the keys arrive in strictly ascending order they're collected and bulk loaded in `O(n)`,
+/// and the rest are inserted one by one from the first out of order key.
+impl<K: Ord, V, const CAP: usize> FromIterator<(K, V)> for BTreeMap<K, V, CAP> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = BTreeMap::new();
+        let mut iter = iter.into_iter();
+        let mut sorted: Vec<(K, V)> = Vec::with_capacity(iter.size_hint().0);
+
+        let unsorted = loop {
+            match iter.next() {
+                Some(entry) if sorted.last().is_some_and(|last| last.0 >= entry.0) => {
+                    break Some(entry)
+                }
+                Some(entry) => sorted.push(entry),
+                None => break None,
+            }
+        };
+
+        map.load_sorted(sorted);
+        map.extend(unsorted.into_iter().chain(iter));
+        map
+    }
+}
+
+/// On duplicate keys, the later entry overwrites the existing one.
+impl<K: Ord, V, const CAP: usize> Extend<(K, V)> for BTreeMap<K, V, CAP> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl<'a, K: Ord + Copy, V: Copy, const CAP: usize> Extend<(&'a K, &'a V)> for BTreeMap<K, V, CAP> {
+    fn extend<I: IntoIterator<Item = (&'a K, &'a V)>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().map(|(key, value)| (*key, *value)));
+    }
+}
+
+impl<K: Ord, V, const CAP: usize> IntoIterator for BTreeMap<K, V, CAP> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, CAP>;
+
+    #[inline]
+    fn into_iter(mut self) -> Self::IntoIter {
+        let length = self.length;
+        IntoIter::new(self.take_leaves(), length)
+    }
+}
+
+impl<'a, K: Ord, V, const CAP: usize> IntoIterator for &'a BTreeMap<K, V, CAP> {
+    type Item = (Ref<'a, K>, Ref<'a, V>);
+    type IntoIter = Iter<'a, K, V, CAP>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, K: Ord, V, const CAP: usize> IntoIterator for &'a mut BTreeMap<K, V, CAP> {
+    type Item = (RefKey<'a, K>, RefMut<'a, V>);
+    type IntoIter = IterMut<'a, K, V, CAP>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, const CAP: usize> fmt::Debug for BTreeMap<K, V, CAP> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut map = f.debug_map();
+        let mut leaf = self.root.as_ref().map(|root| root.first_leaf().leaf_ref());
+        while let Some(current) = leaf {
+            let entries = current.entries();
+            map.entries(entries.iter().map(|(k, v)| (k, v)));
+            leaf = current.next();
+        }
+        map.finish()
+    }
+}
+
+/// Same as `get_ref`, but panics if the key doesn't exist.
+///
+/// Only available with the `"unchecked"` feature for the same reason as the `get_ref`,
+/// since the `Index` trait requires to return a plain reference.
+#[cfg(feature = "unchecked")]
+impl<K, Q, V, const CAP: usize> core::ops::Index<&Q> for BTreeMap<K, V, CAP>
+where
+    K: Ord + core::borrow::Borrow<Q>,
+    Q: Ord,
+{
+    type Output = V;
+
+    #[inline]
+    fn index(&self, query: &Q) -> &V {
+        self.get_ref(query).expect("key not found")
+    }
+}
+
+/// Maps are equal if they have the same entries, regardless of their `CAP`s.
+impl<K: PartialEq, V: PartialEq, const CAP: usize, const CAP2: usize>
+    PartialEq<BTreeMap<K, V, CAP2>> for BTreeMap<K, V, CAP>
+{
+    fn eq(&self, other: &BTreeMap<K, V, CAP2>) -> bool {
+        // Follows the leaf chains same as the `Debug`, as the `iter` requires `K: Ord`.
+        fn entries<K, V, const CAP: usize>(
+            map: &BTreeMap<K, V, CAP>,
+        ) -> impl Iterator<Item = (Ref<'_, K>, Ref<'_, V>)> {
+            let first = map.root.as_ref().map(|root| root.first_leaf().leaf_ref());
+            core::iter::successors(first, |leaf| leaf.next())
+                .flat_map(|leaf| (0..leaf.entries().len()).map(move |idx| leaf.entry(idx)))
+        }
+
+        self.length == other.length
+            && entries(self)
+                .zip(entries(other))
+                .all(|((lk, lv), (rk, rv))| *lk == *rk && *lv == *rv)
+    }
+}
+
+impl<K: Eq, V: Eq, const CAP: usize> Eq for BTreeMap<K, V, CAP> {}
+
+/// Compares the entries lexicographically in ascending key order, regardless of the `CAP`s.
+impl<K: Ord, V: PartialOrd, const CAP: usize, const CAP2: usize> PartialOrd<BTreeMap<K, V, CAP2>>
+    for BTreeMap<K, V, CAP>
+{
+    #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+    fn partial_cmp(&self, other: &BTreeMap<K, V, CAP2>) -> Option<Ordering> {
+        let mut rhs = other.iter();
+        for (lk, lv) in self.iter() {
+            let (rk, rv) = match rhs.next() {
+                Some(entry) => entry,
+                None => return Some(Ordering::Greater),
+            };
+            match K::cmp(&lk, &rk) {
+                Ordering::Equal => {}
+                ord => return Some(ord),
+            }
+            match V::partial_cmp(&lv, &rv)? {
+                Ordering::Equal => {}
+                ord => return Some(ord),
+            }
+        }
+
+        match rhs.next() {
+            Some(_) => Some(Ordering::Less),
+            None => Some(Ordering::Equal),
+        }
+    }
+}
+
+impl<K: Ord, V: Ord, const CAP: usize> Ord for BTreeMap<K, V, CAP> {
+    #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+    fn cmp(&self, other: &Self) -> Ordering {
+        let mut rhs = other.iter();
+        for (lk, lv) in self.iter() {
+            let (rk, rv) = match rhs.next() {
+                Some(entry) => entry,
+                None => return Ordering::Greater,
+            };
+            match K::cmp(&lk, &rk).then_with(|| V::cmp(&lv, &rv)) {
+                Ordering::Equal => {}
+                ord => return ord,
+            }
+        }
+
+        match rhs.next() {
+            Some(_) => Ordering::Less,
+            None => Ordering::Equal,
+        }
+    }
+}
+
+/// Hashes the entries in ascending key order, so equal maps have the same hash.
+impl<K: Ord + Hash, V: Hash, const CAP: usize> Hash for BTreeMap<K, V, CAP> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for (key, value) in self.iter() {
+            key.hash(state);
+            value.hash(state);
+        }
+    }
+}
+
+/// Splits the items into the fewest chunks with the sizes as even as possible.
+fn distribute<T, const CAP: usize>(items: Vec<T>) -> Vec<ArrayVec<T, CAP>> {
+    let len = items.len();
+    let count = len.div_ceil(CAP);
+    let mut items = items.into_iter();
+
+    (0..count)
+        .map(|idx| {
+            let size = len / count + usize::from(idx < len % count);
+            items.by_ref().take(size).collect()
+        })
+        .collect()
+}
+
+fn insert_or_split<T, const CAP: usize>(
+    buf: &mut ArrayVec<T, CAP>,
+    idx: usize,
+    new: T,
+) -> Option<ArrayVec<T, CAP>> {
+    if !buf.is_full() {
+        buf.insert(idx, new);
+        return None;
+    }
+
+    let mut new_buf = ArrayVec::new();
+    let b = CAP.div_ceil(2);
+
+    if idx < b {
+        new_buf.extend(buf.drain(b - 1..));
+        buf.insert(idx, new);
+    } else {
+        new_buf.extend(buf.drain(b..));
+        new_buf.insert(idx - b, new);
+    }
+
+    Some(new_buf)
+}
+
+/// Moves the items between two adjacent nodes so both have at least `CAP.div_ceil(2)` of them,
+/// or moves all the items of `next` into `this` if they fit into one node.
+/// Returns `true` if the `next` is drained.
+fn redistribute<T, const CAP: usize>(
+    this: &mut ArrayVec<T, CAP>,
+    next: &mut ArrayVec<T, CAP>,
+) -> bool {
+    let total = this.len() + next.len();
+    if total <= CAP {
+        this.extend(next.drain(..));
+        return true;
+    }
+
+    let target = total / 2;
+    if this.len() < target {
+        let moved = target - this.len();
+        this.extend(next.drain(..moved));
+    } else {
+        let mut moved: ArrayVec<_, CAP> = this.drain(target..).collect();
+        moved.extend(next.drain(..));
+        *next = moved;
+    }
+    false
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(clippy::needless_option_as_deref))]
+fn check_same_behavior_with_std_btreemap() {
+    let mut m1 = std::collections::BTreeMap::new();
+    let mut m2 = BTreeMap::<_, _, 15>::new();
+
+    let nums: Vec<u32> = std::iter::repeat_with(rand::random)
+        .take(1024 * 1024)
+        .collect();
+
+    for (step, &n) in nums.iter().enumerate() {
+        assert_eq!(m1.insert(n, n), m2.insert(n, n));
+        if step.is_multiple_of(64 * 1024) {
+            m2.check_invariants();
+        }
+    }
+    m2.check_invariants();
+    for &n in &nums {
+        assert_eq!(m1.get(&n), m2.get(&n).as_deref());
+        assert_eq!(
+            m1.get(&n.wrapping_add(1)),
+            m2.get(&n.wrapping_add(1)).as_deref()
+        );
+    }
+    for (step, &n) in nums.iter().enumerate() {
+        assert_eq!(m1.remove(&n), m2.remove(&n));
+        if step.is_multiple_of(64 * 1024) {
+            m2.check_invariants();
+        }
+    }
+    m2.check_invariants();
+    assert!(m2.is_empty());
+}
+
+#[test]
+fn remove_with_info_reports_height_decrease() {
+    let mut map = BTreeMap::<_, _, 5>::new();
+
+    let nums: Vec<u32> = std::iter::repeat_with(rand::random).take(4096).collect();
+    for &n in &nums {
+        map.insert(n, n);
+    }
+
+    let mut shrunk_count = 0;
+    for &n in &nums {
+        let before = map.height();
+        if let Some((key, value, shrunk)) = map.remove_with_info(&n) {
+            assert_eq!((key, value), (n, n));
+            assert_eq!(shrunk, map.height() < before);
+            shrunk_count += shrunk as usize;
+        } else {
+            assert_eq!(map.height(), before);
+        }
+    }
+
+    assert!(map.is_empty());
+    assert_eq!(map.height(), 0);
+    assert!(shrunk_count > 1);
+}
+
+#[test]
+fn try_new_reports_capacity_error() {
+    assert_eq!(
+        BTreeMap::<u32, u32, 3>::try_new().unwrap_err(),
+        CapacityError::TooSmall
+    );
+    assert!(BTreeMap::<u32, u32, 4>::try_new().is_ok());
+    assert!(BTreeMap::<u32, u32, 15>::try_new().is_ok());
+
+    assert_eq!(
+        BTreeMap::<u64, [u8; 4096], 101>::try_new().unwrap_err(),
+        CapacityError::TooLarge
+    );
+    assert!(BTreeMap::<u64, [u8; 4096], 31>::try_new().is_ok());
+    assert!(BTreeMap::<u64, u64, 1001>::try_new().is_ok());
+}
+
+#[test]
+fn iter_ranked_yields_positions_in_order() {
+    let mut map = BTreeMap::<_, _, 5>::new();
+    for _ in 0..4096 {
+        let n: u16 = rand::random();
+        map.insert(n, n);
+    }
+
+    let mut prev = None;
+    for (expected, (rank, key, _)) in map.iter_ranked().enumerate() {
+        assert_eq!(rank, expected);
+        assert!(prev < Some(*key));
+        prev = Some(*key);
+    }
+    assert_eq!(map.iter_ranked().count(), map.len());
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn range_mut_array_mutates_all_handles() {
+    let mut map: BTreeMap<i32, i32, 5> = BTreeMap::new();
+    for i in 0..100 {
+        map.insert(i, i);
+    }
+
+    let [a, b, c, d] = map.range_mut_array(10..=40);
+    let handles = [a.unwrap(), b.unwrap(), c.unwrap(), d.unwrap()];
+    for (key, mut value) in handles {
+        *value += 1000 * *key;
+    }
+
+    {
+        let [a, b, c, d] = map.range_mut_array(97..);
+        assert!(d.is_none());
+        *a.unwrap().1 = -1;
+        *b.unwrap().1 = -2;
+        *c.unwrap().1 = -3;
+    }
+
+    let expected: Vec<_> = (0..100)
+        .map(|i| match i {
+            10..=13 => (i, i + 1000 * i),
+            97 => (i, -1),
+            98 => (i, -2),
+            99 => (i, -3),
+            _ => (i, i),
+        })
+        .collect();
+    let actual: Vec<_> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    assert_eq!(expected, actual);
+    assert_eq!(
+        map.range_mut(50..50).count() + map.range_mut(200..).count(),
+        0
+    );
+}
+
+#[test]
+fn retain_map_transforms_and_drops() {
+    let mut map: BTreeMap<u32, String, 5> = BTreeMap::new();
+    let mut reference = Vec::new();
+    for i in 0..1000 {
+        map.insert(i, i.to_string());
+        reference.push((i, i.to_string()));
+    }
+
+    let f = |key: &u32, value: String| match key % 3 {
+        0 => None,
+        1 => Some(value + "!"),
+        _ => Some(value),
+    };
+    map.retain_map(f);
+    let reference: Vec<_> = reference
+        .into_iter()
+        .filter_map(|(key, value)| f(&key, value).map(|value| (key, value)))
+        .collect();
+
+    assert_eq!(map.len(), reference.len());
+    let actual: Vec<_> = map.iter().map(|(k, v)| (*k, v.clone())).collect();
+    assert_eq!(actual, reference);
+
+    map.retain_map(|_, _| None);
+    assert!(map.is_empty());
+    assert_eq!(map.iter().count(), 0);
+}
+
+#[test]
+fn retain_keeps_unvisited_entries_on_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i, i)).collect();
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        map.retain(|key, _| match *key {
+            500 => panic!("retain"),
+            key => key % 2 == 0,
+        })
+    }));
+    assert!(res.is_err());
+    map.check_invariants();
+    let expected: Vec<_> = (0..500).step_by(2).chain(500..1000).collect();
+    assert!(map.iter().map(|(k, _)| *k).eq(expected.iter().copied()));
+    assert_eq!(map.len(), expected.len());
+}
+
+#[test]
+fn retain_map_keeps_unvisited_entries_on_panic() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    let mut map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i, i)).collect();
+    let res = catch_unwind(AssertUnwindSafe(|| {
+        map.retain_map(|key, value| match *key {
+            700 => panic!("retain_map"),
+            key if key % 3 == 0 => None,
+            _ => Some(value + 1),
+        })
+    }));
+    assert!(res.is_err());
+    map.check_invariants();
+    let expected: Vec<_> = (0..1000)
+        .filter_map(|key| match key {
+            ..700 => (key % 3 != 0).then_some((key, key + 1)),
+            700 => None,
+            _ => Some((key, key)),
+        })
+        .collect();
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq(expected.iter().copied()));
+    assert_eq!(map.len(), expected.len());
+}
+
+#[test]
+fn default_is_empty() {
+    assert!(BTreeMap::<i32, i32, 15>::default().is_empty());
+
+    #[derive(Default)]
+    struct Wrapper {
+        map: BTreeMap<i32, i32, 5>,
+    }
+    assert_eq!(Wrapper::default().map.len(), 0);
+}
+
+#[test]
+#[should_panic]
+fn default_panics_on_small_cap() {
+    let _ = BTreeMap::<i32, i32, 3>::default();
+}
+
+#[cfg(feature = "std-compat")]
+#[test]
+fn map_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<BTreeMap<i32, i32, 15>>();
+    assert_send_sync::<BTreeSet<i32, 15>>();
+}
+
+#[test]
+fn clone_from_is_equal_and_independent() {
+    let mut source: BTreeMap<u32, String, 5> = (0..1000).map(|i| (i, i.to_string())).collect();
+
+    for len in [0, 10, 1000, 5000] {
+        let mut map: BTreeMap<u32, String, 5> = (0..len).map(|i| (i * 3, String::new())).collect();
+        map.clone_from(&source);
+        map.check_invariants();
+        assert_eq!(map, source);
+
+        map.insert(5000, "5000".into());
+        *map.get_mut(&0).unwrap() = "zero".into();
+        source.remove(&1);
+        assert_eq!(source.len(), 999);
+        assert_eq!(*source.get(&0).unwrap(), "0");
+        assert!(!source.contains_key(&5000));
+        assert!(map.contains_key(&1));
+        source.insert(1, "1".into());
+    }
+}
+
+#[test]
+fn compact_improves_fill_ratio() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..10_000).map(|i| (i * 7919 % 10_000, i)).collect();
+    map.retain(|key, _| key % 3 != 0);
+    for i in (0..10_000).filter(|i| i % 3 == 1) {
+        map.remove(&i);
+    }
+    let before = map.stats();
+
+    map.compact();
+    let after = map.stats();
+
+    map.check_invariants();
+    assert_eq!(map.len(), 3333);
+    assert!(map
+        .keys()
+        .zip((0..10_000).filter(|i| i % 3 == 2))
+        .all(|(k, e)| *k == e));
+    assert!(after.fill_ratio > before.fill_ratio);
+    assert!(after.fill_ratio > 0.99);
+    assert!(after.leaf_count < before.leaf_count);
+    assert!(after.depth <= before.depth);
+}
+
+#[test]
+fn node_capacity_and_min_entries() {
+    assert_eq!(BTreeMap::<u32, u32, 5>::node_capacity(), 5);
+    assert_eq!(BTreeMap::<u32, u32, 5>::min_entries(), 3);
+    assert_eq!(BTreeMap15::<u32, u32>::min_entries(), 15 / 2 + 1);
+    assert_eq!(BTreeMap31::<u32, u32>::min_entries(), 31 / 2 + 1);
+    assert_eq!(BTreeMap::<u32, u32, 16>::min_entries(), 8);
+    assert_eq!(BTreeMap::<u32, u32, 4>::min_entries(), 2);
+}
+
+#[test]
+fn with_expected_len_is_same_as_new() {
+    let mut map = BTreeMap::<u32, u32, 5>::with_expected_len(1000);
+    let mut expected = BTreeMap::<u32, u32, 5>::new();
+    assert_eq!(map, expected);
+
+    for i in 0..1000 {
+        map.insert(i * 7919 % 1000, i);
+        expected.insert(i * 7919 % 1000, i);
+    }
+    assert_eq!(map, expected);
+    assert_eq!(map.stats(), expected.stats());
+}
+
+#[test]
+#[should_panic]
+fn with_expected_len_panics_on_small_cap() {
+    let _ = BTreeMap::<i32, i32, 3>::with_expected_len(10);
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn get_or_insert_with_calls_closure_only_on_absence() {
+    let mut map = BTreeMap::<u32, u32, 5>::new();
+    let mut calls = 0;
+
+    for i in (0..1000).chain(0..1000) {
+        let mut value = map.get_or_insert_with(i % 500, || {
+            calls += 1;
+            0
+        });
+        *value += 1;
+    }
+
+    assert_eq!(calls, 500);
+    assert_eq!(map.len(), 500);
+    assert!(map.values().all(|v| *v == 4));
+    map.check_invariants();
+}
+
+#[test]
+fn get_or_insert_default_counts_keys() {
+    let mut map = BTreeMap::<u32, u32, 5>::new();
+    let stream = (0..5000).map(|i| i * 7919 % 1000 / 3);
+
+    for key in stream.clone() {
+        *map.get_or_insert_default(key) += 1;
+    }
+
+    let mut expected = std::collections::BTreeMap::new();
+    for key in stream {
+        *expected.entry(key).or_insert(0) += 1;
+    }
+    map.check_invariants();
+    assert_eq!(map.len(), expected.len());
+    assert!(map.iter().map(|(k, v)| (*k, *v)).eq(expected.into_iter()));
+}
+
+#[test]
+fn iter_from_matches_range_from() {
+    let map: BTreeMap<u32, u32, 5> = (0..500).map(|i| (i * 4 + 10, i)).collect();
+
+    for start in (0..2020).step_by(3) {
+        let iter = map.iter_from(&start);
+        assert_eq!(iter.len(), map.count_range(start..));
+        let expected: Vec<_> = map.range(start..).map(|(k, v)| (*k, *v)).collect();
+        assert!(iter.map(|(k, v)| (*k, *v)).eq(expected.iter().copied()));
+        assert!(map
+            .iter_from(&start)
+            .rev()
+            .map(|(k, _)| *k)
+            .eq(expected.iter().rev().map(|&(k, _)| k)));
+    }
+
+    assert!(map
+        .iter_from(&0)
+        .map(|(k, _)| *k)
+        .eq(map.iter().map(|(k, _)| *k)));
+    assert_eq!(map.iter_from(&0).len(), 500);
+    assert!(map.iter_from(&5000).next().is_none());
+    assert_eq!(BTreeMap::<u32, u32, 5>::new().iter_from(&0).len(), 0);
+}
+
+#[test]
+fn ord_is_lexicographic() {
+    let map =
+        |entries: &[(u32, u32)]| -> BTreeMap<u32, u32, 5> { entries.iter().copied().collect() };
+    let long: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i, i)).collect();
+    let prefix: BTreeMap<u32, u32, 7> = (0..50).map(|i| (i, i)).collect();
+
+    assert!(prefix < long);
+    assert!(long > prefix);
+    assert_eq!(long.partial_cmp(&long.clone()), Some(Ordering::Equal));
+    assert!(BTreeMap::<u32, u32, 5>::new() < map(&[(0, 0)]));
+
+    assert!(map(&[(1, 0), (2, 0)]) < map(&[(1, 1)]));
+    assert!(map(&[(1, 9), (2, 0)]) > map(&[(1, 1), (2, 0)]));
+    assert!(map(&[(1, 0)]) < map(&[(2, 0)]));
+
+    let mut maps = vec![map(&[(2, 0)]), long.clone(), map(&[(0, 1)]), map(&[])];
+    maps.sort();
+    assert!(maps == [map(&[]), long, map(&[(0, 1)]), map(&[(2, 0)])]);
+    let floats: BTreeMap<u32, f64, 5> = [(0, f64::NAN)].into_iter().collect();
+    assert_eq!(floats.partial_cmp(&floats), None);
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn borrowed_map_into_iter() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i * 7 % 100, 0)).collect();
+
+    for (key, mut value) in &mut map {
+        *value = *key * 2;
+    }
+
+    let mut expected = 0;
+    for (key, value) in &map {
+        assert_eq!((*key, *value), (expected, expected * 2));
+        expected += 1;
+    }
+    assert_eq!(expected, 100);
+    assert!((&map).into_iter().rev().map(|(k, _)| *k).eq((0..100).rev()));
+}
+
+#[test]
+fn debug_prints_entries() {
+    let mut map = BTreeMap::<u32, &str, 5>::new();
+    assert_eq!(format!("{:?}", map), "{}");
+
+    for (i, name) in ["zero", "one", "two", "three", "four", "five", "six"]
+        .into_iter()
+        .enumerate()
+    {
+        map.insert(i as u32, name);
+    }
+    // The entries span two leaves.
+    assert_eq!(
+        format!("{:?}", map),
+        r#"{0: "zero", 1: "one", 2: "two", 3: "three", 4: "four", 5: "five", 6: "six"}"#
+    );
+}
+
+#[test]
+fn eq_compares_entries() {
+    let map: BTreeMap<u32, u32, 5> = (0..500).map(|i| (i, i * 2)).collect();
+    let mut other: BTreeMap<u32, u32, 7> = (0..500).rev().map(|i| (i, i * 2)).collect();
+    assert!(map == other);
+    assert!(map == map.clone());
+
+    other.insert(250, 0);
+    assert!(map != other);
+
+    other.insert(250, 500);
+    other.insert(500, 1000);
+    assert!(map != other);
+    other.remove(&500);
+    assert!(map == other);
+    assert!(map != BTreeMap::<u32, u32, 5>::new());
+
+    // Only the `PartialEq` of the keys is needed.
+    fn is_partial_eq<T: PartialEq<U>, U>() {}
+    is_partial_eq::<BTreeMap<f64, f64, 5>, BTreeMap<f64, f64, 7>>();
+}
+
+#[test]
+// Leaf cells are only mutated through the `&mut BTreeMap`.
+#[allow(clippy::mutable_key_type)]
+fn hash_ignores_insertion_order() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::HashSet;
+
+    fn hash_of<T: Hash>(value: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let forward: BTreeMap<u32, u32, 5> = (0..500).map(|i| (i, i * 2)).collect();
+    let shuffled: BTreeMap<u32, u32, 5> =
+        (0..500).map(|i| i * 7 % 500).map(|i| (i, i * 2)).collect();
+    assert_eq!(hash_of(&forward), hash_of(&shuffled));
+
+    let mut set = HashSet::new();
+    set.insert(forward);
+    assert!(set.contains(&shuffled));
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(clippy::needless_option_as_deref))]
+fn clone_iterates_identically() {
+    let mut map: BTreeMap<u32, String, 5> = BTreeMap::new();
+    for i in (0..1000).map(|i| i * 7919 % 1000) {
+        map.insert(i, i.to_string());
+    }
+    let mut cloned = map.clone();
+
+    assert_eq!(cloned.len(), map.len());
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, v.clone()))
+        .eq(cloned.iter().map(|(k, v)| (*k, v.clone()))));
+
+    cloned.insert(1000, "new".into());
+    cloned.remove(&0);
+    assert_eq!(map.len(), 1000);
+    assert_eq!(map.get(&0).as_deref().map(|v| v.as_str()), Some("0"));
+    assert!(map.get(&1000).is_none());
+    assert!(cloned.iter().map(|(k, _)| *k).eq(1..=1000));
+
+    let empty: BTreeMap<u32, u32, 5> = BTreeMap::new();
+    assert_eq!(empty.clone().iter().count(), 0);
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn clone_survives_mutating_original() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i, i)).collect();
+    let cloned = map.clone();
+
+    map.remove_range(100..900);
+    map.retain(|k, _| k % 2 == 0);
+    for mut v in map.values_mut() {
+        *v += 1;
+    }
+    map.check_invariants();
+
+    cloned.check_invariants();
+    assert!(cloned
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq((0..1000).map(|i| (i, i))));
+    assert!(cloned.iter().rev().map(|(k, _)| *k).eq((0..1000).rev()));
+    assert!(cloned.range(250..750).map(|(k, _)| *k).eq(250..750));
+}
+
+#[test]
+fn get_cloned_outlives_the_borrow() {
+    let mut map: BTreeMap<u32, Vec<u8>, 5> = (0..100).map(|i| (i, vec![i as u8; 3])).collect();
+
+    let value = map.get_cloned(&42).unwrap();
+    // The map can be mutated while the clone is alive.
+    map.get_mut(&42).unwrap().push(0);
+    map.remove(&43);
+    assert_eq!(value, [42, 42, 42]);
+    assert_eq!(map.get_cloned(&42).unwrap(), [42, 42, 42, 0]);
+    assert_eq!(map.get_cloned(&43), None);
+    assert_eq!(map.get_cloned(&1000), None);
+}
+
+#[cfg(feature = "unchecked")]
+#[test]
+fn get_ref_returns_plain_reference() {
+    let mut map: BTreeMap<u32, String, 5> = BTreeMap::new();
+    for i in 0..100 {
+        map.insert(i, i.to_string());
+    }
+
+    let values: Vec<&String> = (0..100).filter_map(|i| map.get_ref(&i)).collect();
+    assert_eq!(values.len(), 100);
+    assert_eq!(values[42], "42");
+    assert_eq!(map.get_ref(&100), None);
+}
+
+#[cfg(feature = "unchecked")]
+#[test]
+fn get_and_entry_are_plain_references() {
+    struct Borrowed<'a> {
+        key: &'a u32,
+        value: &'a String,
+    }
+
+    let mut map: BTreeMap<u32, String, 5> = (0..100).map(|i| (i, i.to_string())).collect();
+    let value: &String = map.get(&7).unwrap();
+    let (key, other): (&u32, &String) = map.entry(&8).unwrap();
+    let stored = Borrowed { key, value };
+    assert_eq!(
+        (*stored.key, stored.value.as_str(), other.as_str()),
+        (8, "7", "8")
+    );
+
+    let value: &mut String = map.get_mut(&9).unwrap();
+    value.push('!');
+    assert_eq!(map.get(&9).map(String::as_str), Some("9!"));
+}
+
+#[cfg(feature = "unchecked")]
+#[test]
+fn index_returns_value() {
+    let map: BTreeMap<u32, String, 5> = (0..100).map(|i| (i, i.to_string())).collect();
+    assert_eq!(map[&0], "0");
+    assert_eq!(map[&42], "42");
+    assert_eq!(map[&99].len(), 2);
+}
+
+#[cfg(feature = "unchecked")]
+#[test]
+#[should_panic(expected = "key not found")]
+fn index_panics_on_missing_key() {
+    let map: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i * 2, i)).collect();
+    let _ = map[&41];
+}
+
+#[test]
+fn rank_and_select_match_sorted_vec() {
+    let mut map = BTreeMap::<u32, u32, 5>::new();
+    let mut sorted: Vec<u32> = vec![];
+    for _ in 0..4096 {
+        let n = rand::random::<u32>() % 2048;
+        if rand::random::<u8>() < 64 {
+            map.remove(&n);
+            sorted.retain(|&k| k != n);
+        } else if map.insert(n, n * 2).is_none() {
+            sorted.insert(sorted.partition_point(|&k| k < n), n);
+        }
+    }
+    map.check_invariants();
+
+    for query in 0..2050 {
+        assert_eq!(map.rank(&query), sorted.partition_point(|&k| k < query));
+    }
+    for (n, key) in sorted.iter().enumerate() {
+        assert_eq!(map.select(n).map(|(k, v)| (*k, *v)), Some((*key, key * 2)));
+    }
+    assert!(map.select(sorted.len()).is_none());
+
+    while let Some((key, _)) = map.pop_first() {
+        assert_eq!(map.rank(&key), 0);
+        assert_eq!(map.rank(&u32::MAX), map.len());
+    }
+    map.check_invariants();
+    assert!(map.select(0).is_none());
+}
+
+#[test]
+fn partition_point_matches_linear_scan() {
+    // Composite keys of (minute, sequence), queried by the time window only.
+    let map: BTreeMap<(u32, u32), u32, 5> =
+        (0..5000).map(|i| ((i * 7919 % 5000 / 7, i), i)).collect();
+
+    for minute in [0, 1, 100, 357, 713, 714, 1000] {
+        let expected = map.keys().take_while(|key| key.0 < minute).count();
+        assert_eq!(map.partition_point(|key| key.0 < minute), expected);
+    }
+    assert_eq!(map.partition_point(|_| true), map.len());
+    assert_eq!(map.partition_point(|_| false), 0);
+    assert_eq!(BTreeMap::<u32, u32, 5>::new().partition_point(|_| true), 0);
+}
+
+#[test]
+fn position_range_matches_range() {
+    let map: BTreeMap<u32, u32, 5> = (0..2000).map(|i| (i * 7919 % 2000 * 2, i)).collect();
+
+    for (start, end) in [(0, 0), (1, 3999), (100, 101), (1001, 2400), (3000, 9000)] {
+        let positions = map.position_range(start..end);
+        assert_eq!(positions.len(), map.range(start..end).count());
+        assert_eq!(positions.start, map.range(..start).count());
+        for n in positions {
+            let key = *map.nth(n).unwrap().0;
+            assert!((start..end).contains(&key));
+        }
+    }
+    let (lo, hi) = (10, 5);
+    assert_eq!(map.position_range(lo..hi), 5..5);
+}
+
+#[test]
+fn count_range_matches_range() {
+    let map: BTreeMap<u32, u32, 5> = (0..2000)
+        .map(|i| i * 7919 % 2000)
+        .filter(|key| key % 3 != 0)
+        .map(|key| (key, key))
+        .collect();
+
+    macro_rules! check {
+        ($($range:expr),*) => {$(
+            assert_eq!(map.count_range($range), map.range($range).count(), "{:?}", $range);
+        )*};
+    }
+    check!(
+        300..900,
+        299..=901,
+        300..300,
+        300..=300,
+        301..=301,
+        ..900,
+        ..=0,
+        ..,
+        1200..,
+        2000..,
+        (Bound::Excluded(300), Bound::Included(900)),
+        (Bound::Excluded(301), Bound::Excluded(302))
+    );
+
+    for _ in 0..1000 {
+        let (start, end) = (rand::random::<u32>() % 2100, rand::random::<u32>() % 2100);
+        let expected = match start <= end {
+            true => map.range(start..end).count(),
+            false => 0,
+        };
+        assert_eq!(map.count_range(start..end), expected);
+    }
+    let (start, end) = (900, 300);
+    assert_eq!(map.count_range(start..end), 0);
+    assert_eq!(
+        map.count_range((Bound::Excluded(300), Bound::Excluded(300))),
+        0
+    );
+    assert_eq!(BTreeMap::<u32, u32, 5>::new().count_range(..), 0);
+}
+
+#[test]
+fn range_is_empty_over_gaps() {
+    // Keys are in the clusters [0, 10), [100, 110), .. [900, 910).
+    let map: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i / 10 * 100 + i % 10, i)).collect();
+
+    for start in 0..1000 {
+        for len in [0, 1, 5, 90, 91, 200] {
+            let range = start..start + len;
+            assert_eq!(
+                map.range_is_empty(range.clone()),
+                map.count_range(range.clone()) == 0
+            );
+            let range = start..=start + len;
+            assert_eq!(
+                map.range_is_empty(range.clone()),
+                map.count_range(range) == 0
+            );
+        }
+        assert_eq!(map.range_is_empty(start..), start >= 910);
+        assert_eq!(map.range_is_empty(..start), start == 0);
+    }
+
+    assert!(map.range_is_empty(10..100));
+    assert!(!map.range_is_empty(10..=100));
+    assert!(map.range_is_empty((Bound::Excluded(9), Bound::Excluded(100))));
+    assert!(!map.range_is_empty::<u32, _>(..));
+    #[allow(clippy::reversed_empty_ranges)]
+    let inverted = 105..100;
+    assert!(map.range_is_empty(inverted));
+    assert!(BTreeMap::<u32, u32, 5>::new().range_is_empty::<u32, _>(..));
+}
+
+#[test]
+fn lower_and_upper_bound() {
+    let map: BTreeMap<u32, u32, 5> = (0..500).map(|i| (i * 4, i)).collect();
+    let key = |entry: Option<(Ref<'_, u32>, Ref<'_, u32>)>| entry.map(|(k, _)| *k);
+
+    for query in 0..2000u32 {
+        let lower = query.div_ceil(4) * 4;
+        let upper = (query / 4 + 1) * 4;
+        assert_eq!(
+            key(map.lower_bound(&query)),
+            Some(lower).filter(|&k| k < 2000)
+        );
+        assert_eq!(
+            key(map.upper_bound(&query)),
+            Some(upper).filter(|&k| k < 2000)
+        );
+    }
+
+    assert_eq!(key(map.lower_bound(&1996)), Some(1996));
+    assert_eq!(key(map.upper_bound(&1996)), None);
+    assert_eq!(key(map.lower_bound(&1997)), None);
+    assert_eq!(key(map.lower_bound(&u32::MAX)), None);
+    assert!(BTreeMap::<u32, u32, 5>::new().lower_bound(&0).is_none());
+}
+
+#[test]
+fn floor_and_ceil_on_sparse_keys() {
+    let keys: Vec<u32> = (0..3000).filter(|i| i % 7 == 0 || i % 11 == 0).collect();
+    let map: BTreeMap<u32, u32, 5> = keys.iter().map(|&k| (k, k * 2)).collect();
+    let key = |entry: Option<(Ref<'_, u32>, Ref<'_, u32>)>| entry.map(|(k, _)| *k);
+
+    for query in 0..3100 {
+        let floor = keys[..keys.partition_point(|&k| k <= query)]
+            .last()
+            .copied();
+        let ceil = keys.get(keys.partition_point(|&k| k < query)).copied();
+        assert_eq!(key(map.floor(&query)), floor, "floor of {}", query);
+        assert_eq!(key(map.ceil(&query)), ceil, "ceil of {}", query);
+    }
+    assert_eq!(map.floor(&700).map(|(_, v)| *v), Some(1400));
+
+    let map: BTreeMap<u32, u32, 5> = (1..100).map(|i| (i * 10, i)).collect();
+    assert!(map.floor(&9).is_none());
+    assert_eq!(key(map.floor(&u32::MAX)), Some(990));
+    assert!(map.ceil(&991).is_none());
+}
+
+#[test]
+fn predecessor_and_successor_are_strict() {
+    let map: BTreeMap<u32, u32, 5> = (1..=300).map(|i| (i * 3, i)).collect();
+    let key = |entry: Option<(Ref<'_, u32>, Ref<'_, u32>)>| entry.map(|(k, _)| *k);
+
+    for i in 1..=300 {
+        // On the existing key.
+        let at = i * 3;
+        assert_eq!(key(map.predecessor(&at)), Some(at - 3).filter(|&k| k > 0));
+        assert_eq!(key(map.successor(&at)), Some(at + 3).filter(|&k| k <= 900));
+        assert_eq!(key(map.floor(&at)), Some(at));
+
+        // In the gap.
+        let gap = at + 1;
+        assert_eq!(key(map.predecessor(&gap)), Some(at));
+        assert_eq!(key(map.successor(&gap)), Some(at + 3).filter(|&k| k <= 900));
+    }
+    assert!(map.predecessor(&3).is_none());
+    assert!(map.successor(&900).is_none());
+}
+
+#[test]
+#[cfg_attr(feature = "unchecked", allow(unused_mut))]
+fn get_disjoint_mut_swaps_values() {
+    let mut map: BTreeMap<u32, String, 5> = (0..100).map(|i| (i, i.to_string())).collect();
+
+    // Within the same leaf, and across the leaves in any order.
+    for (a, b) in [(0, 1), (1, 0), (10, 90), (99, 3)] {
+        let [x, y] = map.get_disjoint_mut([&a, &b]);
+        core::mem::swap(&mut *x.unwrap(), &mut *y.unwrap());
+        assert_eq!(*map.get(&a).unwrap(), b.to_string());
+        assert_eq!(*map.get(&b).unwrap(), a.to_string());
+        let [x, y] = map.get_disjoint_mut([&a, &b]);
+        core::mem::swap(&mut *x.unwrap(), &mut *y.unwrap());
+    }
+
+    {
+        let [x, missing, y, z] = map.get_disjoint_mut([&50, &100, &51, &49]);
+        assert!(missing.is_none());
+        for mut value in [x, y, z].into_iter().flatten() {
+            value.push('!');
+        }
+    }
+    assert_eq!(*map.get(&49).unwrap(), "49!");
+    assert_eq!(*map.get(&51).unwrap(), "51!");
+    assert!(map.get_disjoint_mut::<_, 0>([]).is_empty());
+    assert!(matches!(
+        BTreeMap::<u32, u32, 5>::new().get_disjoint_mut([&1]),
+        [None]
+    ));
+}
+
+#[test]
+fn swap_values_of_two_keys() {
+    let mut map: BTreeMap<u32, String, 5> = (0..100).map(|i| (i, i.to_string())).collect();
+
+    assert!(map.swap_values(&3, &97));
+    assert_eq!(*map.get(&3).unwrap(), "97");
+    assert_eq!(*map.get(&97).unwrap(), "3");
+    assert!(map.swap_values(&97, &3));
+    assert_eq!(*map.get(&3).unwrap(), "3");
+
+    // Either key missing leaves the other untouched.
+    assert!(!map.swap_values(&3, &100));
+    assert!(!map.swap_values(&100, &3));
+    assert!(!map.swap_values(&100, &101));
+    assert_eq!(*map.get(&3).unwrap(), "3");
+
+    assert!(map.swap_values(&42, &42));
+    assert_eq!(*map.get(&42).unwrap(), "42");
+    assert!(!map.swap_values(&100, &100));
+    assert!(map.iter().all(|(k, v)| k.to_string() == *v));
+}
+
+#[test]
+fn get_many_mut_mixes_present_and_absent() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i * 2, i)).collect();
+    let keys: Vec<u32> = vec![998, 3, 0, 1500, 1998, 2000, 64, 66];
+
+    {
+        let refs: Vec<&u32> = keys.iter().collect();
+        let mut values = map.get_many_mut(&refs);
+        assert_eq!(values.len(), keys.len());
+        assert!(values[1].is_none() && values[5].is_none());
+        for value in values.iter_mut().flatten() {
+            **value += 10_000;
+        }
+    }
+
+    for key in keys {
+        match map.get(&key) {
+            Some(value) => assert_eq!(*value, key / 2 + 10_000),
+            None => assert!(key % 2 == 1 || key >= 2000),
+        }
+    }
+    assert_eq!(*map.get(&2).unwrap(), 1);
+    assert!(BTreeMap::<u32, u32, 5>::new().get_many_mut(&[&1, &2])[0].is_none());
+}
+
+#[test]
+#[should_panic(expected = "duplicate keys in get_many_mut")]
+fn get_many_mut_panics_on_repeated_keys() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i, i)).collect();
+    let _ = map.get_many_mut(&[&1, &50, &1]);
+}
+
+#[test]
+#[should_panic(expected = "duplicate keys in get_disjoint_mut")]
+fn get_disjoint_mut_panics_on_duplicate_keys() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i, i)).collect();
+    let _ = map.get_disjoint_mut([&1, &2, &1]);
+}
+
+#[test]
+fn nth_reproduces_iter() {
+    let map: BTreeMap<u32, u32, 5> = (0..3000).map(|i| (i * 7919 % 3001, i)).collect();
+
+    assert!((0..map.len())
+        .map(|n| map.nth(n).unwrap())
+        .zip(map.iter())
+        .all(|((k1, v1), (k2, v2))| *k1 == *k2 && *v1 == *v2));
+    assert!(map.nth(map.len()).is_none());
+    assert!(BTreeMap::<u32, u32, 5>::new().nth(0).is_none());
+}
+
+#[test]
+fn append_rebalances_junction_leaves() {
+    fn map_of(keys: core::ops::Range<u32>) -> BTreeMap<u32, u32, 5> {
+        let mut map = BTreeMap::new();
+        for key in keys {
+            map.insert(key, key);
+        }
+        map.check_invariants();
+        map
+    }
+
+    // Boundary leafs at the minimum occupancy, a lone underfull leaf on either side,
+    // and overlapping ranges which take the merging path.
+    for (left, right) in [
+        (0..6, 6..12),
+        (0..6, 6..7),
+        (0..1, 1..7),
+        (0..2, 2..3),
+        (0..100, 100..101),
+        (0..1, 1..100),
+        (0..100, 50..150),
+    ] {
+        let expected: std::collections::BTreeSet<_> = left.clone().chain(right.clone()).collect();
+        let mut map = map_of(left);
+        let mut other = map_of(right);
+
+        map.append(&mut other);
+        map.check_invariants();
+        other.check_invariants();
+        assert!(other.is_empty());
+        assert!(map.iter().map(|(k, _)| *k).eq(expected.iter().copied()));
+    }
+}
+
+#[test]
+fn remove_range_trims_mid_leaf() {
+    fn map_of(len: u32) -> BTreeMap<u32, u32, 7> {
+        let mut map = BTreeMap::new();
+        for key in 0..len {
+            map.insert(key, key);
+        }
+        map
+    }
+
+    // Middle three entries of a single leaf.
+    let mut map = map_of(7);
+    assert_eq!(map.remove_range(2..5), 3);
+    map.check_invariants();
+    assert_eq!(map.len(), 4);
+    assert!(map.iter().map(|(k, _)| *k).eq([0, 1, 5, 6]));
+
+    // Sequential inserts leave the leafs [0..4] [4..8] [8..12] ..
+    let mut map = map_of(40);
+    assert_eq!(map.remove_range(2..=5), 4);
+    map.check_invariants();
+    assert_eq!(map.len(), 36);
+    assert!(map.iter().map(|(k, _)| *k).eq((0..2).chain(6..40)));
+
+    let mut map = map_of(40);
+    assert_eq!(map.remove_range(9..14), 5);
+    map.check_invariants();
+    assert!(map.iter().map(|(k, _)| *k).eq((0..9).chain(14..40)));
+
+    assert_eq!(map.remove_range(9..14), 0);
+    assert_eq!(map.remove_range(..), 35);
+    map.check_invariants();
+    assert!(map.is_empty());
+}
+
+#[test]
+fn peek_keys_are_owned() {
+    let mut map: BTreeMap<String, u32, 5> = BTreeMap::new();
+    assert_eq!(map.peek_first_key(), None);
+    assert_eq!(map.peek_last_key(), None);
+
+    for i in 0..100 {
+        map.insert(format!("key{:03}", i * 37 % 100), i);
+    }
+    let first = map.peek_first_key().unwrap();
+    let last = map.peek_last_key().unwrap();
+    assert_eq!(first, "key000");
+    assert_eq!(last, "key099");
+
+    // The map can be mutated while the keys are still alive.
+    map.remove(&first);
+    map.insert("key100".to_owned(), 100);
+    assert_eq!(map.peek_first_key().unwrap(), "key001");
+    assert_eq!(map.peek_last_key().unwrap(), "key100");
+    assert!(last < map.peek_last_key().unwrap());
+}
+
+#[test]
+fn remove_range_drops_interior_leaves() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..2000).map(|i| (i, i)).collect();
+    assert!(map.stats().leaf_count > 100);
+
+    assert_eq!(map.remove_range(123..=1876), 1754);
+    map.check_invariants();
+    assert_eq!(map.len(), 246);
+    assert!(map.iter().map(|(k, _)| *k).eq((0..123).chain(1877..2000)));
+    assert!(map
+        .iter()
+        .rev()
+        .map(|(k, _)| *k)
+        .eq((1877..2000).rev().chain((0..123).rev())));
+    assert!(map
+        .range(120..1880)
+        .map(|(k, _)| *k)
+        .eq((120..123).chain(1877..1880)));
+    assert!(map.contains_key(&122));
+    assert!(!map.contains_key(&123));
+    assert!(map.contains_key(&1877));
+
+    // Bounds between the remaining keys remove nothing, and leave the tree as is.
+    assert_eq!(map.remove_range(200..1800), 0);
+    assert_eq!(map.remove_range(..0), 0);
+    assert_eq!(map.remove_range(2000..), 0);
+    assert_eq!(map.len(), 246);
+
+    assert_eq!(map.remove_range(..100), 100);
+    assert_eq!(map.remove_range(1900..), 100);
+    map.check_invariants();
+    assert!(map.iter().map(|(k, _)| *k).eq((100..123).chain(1877..1900)));
+}
+
+#[test]
+fn capacity_aliases_behave_like_explicit_form() {
+    let mut explicit: BTreeMap<u32, u32, 15> = BTreeMap::new();
+    let mut small: BTreeMap15<u32, u32> = BTreeMap::new();
+    let mut large: BTreeMap31<u32, u32> = BTreeMap::new();
+    let mut default: DefaultBTreeMap<u32, u32> = DefaultBTreeMap::new();
+
+    for i in (0..1000).map(|i| i * 7919 % 1000) {
+        explicit.insert(i, i);
+        small.insert(i, i);
+        large.insert(i, i);
+        default.insert(i, i);
+    }
+    // The alias names the same type as the explicit form.
+    let cloned: BTreeMap15<u32, u32> = explicit.clone();
+
+    let expected: Vec<_> = explicit.iter().map(|(k, v)| (*k, *v)).collect();
+    assert!(small
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq(expected.iter().copied()));
+    assert!(cloned
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq(expected.iter().copied()));
+    assert!(large
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq(expected.iter().copied()));
+    assert!(default
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq(expected.iter().copied()));
+}
+
+#[test]
+fn first_and_last_key_value() {
+    let mut map: BTreeMap<u32, u32, 5> = BTreeMap::new();
+    assert!(map.first_key_value().is_none());
+    assert!(map.last_key_value().is_none());
+
+    map.insert(7, 70);
+    assert_eq!(map.first_key_value().map(|(k, v)| (*k, *v)), Some((7, 70)));
+    assert_eq!(map.last_key_value().map(|(k, v)| (*k, *v)), Some((7, 70)));
+
+    for key in (0..1000).map(|i| i * 7919 % 1000 + 10) {
+        map.insert(key, key * 10);
+    }
+    assert!(map.height() > 2);
+    assert_eq!(map.first_key_value().map(|(k, v)| (*k, *v)), Some((7, 70)));
+    assert_eq!(
+        map.last_key_value().map(|(k, v)| (*k, *v)),
+        Some((1009, 10090))
+    );
+}
+
+#[test]
+fn first_entry_pops_min_conditionally() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i * 7919 % 1000, i % 3)).collect();
+
+    // Decrement the smallest counter, and remove it once it reaches zero.
+    let mut popped = vec![];
+    while let Some(mut entry) = map.first_entry() {
+        if *entry.get() == 0 {
+            popped.push(entry.remove_entry().0);
+        } else {
+            *entry.get_mut() -= 1;
+        }
+    }
+    assert_eq!(popped, (0..1000).collect::<Vec<_>>());
+    map.check_invariants();
+
+    let mut map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i, i)).collect();
+    while let Some(entry) = map.last_entry() {
+        if *entry.key() < 500 {
+            break;
+        }
+        let (key, value) = entry.remove_entry();
+        assert_eq!(key, value);
+        map.check_invariants();
+    }
+    assert_eq!(map.len(), 500);
+    assert!(BTreeMap::<u32, u32, 5>::new().first_entry().is_none());
+}
+
+#[test]
+fn pop_first_and_last_drain_in_order() {
+    let mut map: BTreeMap<u32, u32, 5> = BTreeMap::new();
+    let nums: Vec<u32> = std::iter::repeat_with(rand::random).take(4096).collect();
+    for &n in &nums {
+        map.insert(n, n);
+    }
+    let mut sorted = nums;
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    let mut popped = vec![];
+    while let Some((key, value)) = map.pop_first() {
+        assert_eq!(key, value);
+        popped.push(key);
+        if popped.len() % 512 == 0 {
+            map.check_invariants();
+        }
+    }
+    assert_eq!(popped, sorted);
+    assert!(map.is_empty());
+    assert_eq!(map.height(), 0);
+
+    for &n in &sorted {
+        map.insert(n, n);
+    }
+    let mut popped = vec![];
+    while let Some((key, _)) = map.pop_last() {
+        popped.push(key);
+    }
+    assert!(popped.iter().eq(sorted.iter().rev()));
+    assert_eq!(map.pop_last(), None);
+}
+
+#[test]
+fn contains_key_reports_presence() {
+    let mut map: BTreeMap<u32, u32, 5> = BTreeMap::new();
+    assert!(!map.contains_key(&0));
+    for key in (0..200).step_by(2) {
+        map.insert(key, key);
+    }
+
+    assert!((0..200).all(|key| map.contains_key(&key) == (key % 2 == 0)));
+    assert!(!map.contains_key(&200));
+    map.remove(&100);
+    assert!(!map.contains_key(&100));
+}
+
+#[test]
+fn get_key_value_matches_entry() {
+    let mut map: BTreeMap<String, u32, 5> = BTreeMap::new();
+    for i in 0..100 {
+        map.insert(format!("{:03}", i), i);
+    }
+
+    let (key, value) = map.get_key_value(&"042".to_string()).unwrap();
+    assert_eq!((key.as_str(), *value), ("042", 42));
+    assert!(map.get_key_value(&"42".to_string()).is_none());
+    assert!((0..100).all(|i| {
+        let key = format!("{:03}", i);
+        map.get_key_value(&key).map(|(k, v)| (k.clone(), *v))
+            == map.entry(&key).map(|(k, v)| (k.clone(), *v))
+    }));
+}
+
+#[test]
+fn clear_drops_every_entry() {
+    use alloc::rc::Rc;
+
+    let counter = Rc::new(());
+    let mut map: BTreeMap<u32, Rc<()>, 5> = BTreeMap::new();
+    for i in 0..1000 {
+        map.insert(i, Rc::clone(&counter));
+    }
+    assert_eq!(Rc::strong_count(&counter), 1001);
+
+    map.clear();
+    assert_eq!(Rc::strong_count(&counter), 1);
+    assert!(map.is_empty());
+    assert_eq!(map.iter().count(), 0);
+
+    map.insert(1, Rc::clone(&counter));
+    assert_eq!(map.len(), 1);
+    drop(map);
+    assert_eq!(Rc::strong_count(&counter), 1);
+}
+
+#[test]
+fn split_off_partitions_entries() {
+    let keys: Vec<u32> = (0..1000).map(|i| i * 7919 % 1000 * 2).collect();
+    for at in [0, 1, 2, 3, 500, 501, 1000, 1997, 1998, 1999, 5000] {
+        let mut map: BTreeMap<u32, u32, 5> = BTreeMap::new();
+        for &key in &keys {
+            map.insert(key, key);
+        }
+
+        let other = map.split_off(&at);
+        map.check_invariants();
+        other.check_invariants();
+        assert!(map.keys().all(|k| *k < at));
+        assert!(other.keys().all(|k| *k >= at));
+        assert_eq!(map.len() + other.len(), keys.len());
+        assert!(map
+            .iter()
+            .chain(other.iter())
+            .map(|(k, _)| *k)
+            .eq((0..1000).map(|i| i * 2)));
+    }
+}
+
+#[test]
+fn split_and_concat_keep_invariants() {
+    fn check<const CAP: usize>() {
+        // Random insertions leave the leafs between half and fully occupied.
+        let map_of = |keys: core::ops::Range<u32>| {
+            let mut map = BTreeMap::<u32, u32, CAP>::new();
+            for i in 0..keys.len() as u32 {
+                let key = keys.start + i * 7919 % keys.len() as u32;
+                map.insert(key, key);
+            }
+            map
+        };
+
+        for len in [1, 2, 3, CAP as u32, CAP as u32 + 1, 50, 300] {
+            let source = map_of(0..len);
+            for n in 0..=len as usize {
+                let mut map = source.clone();
+                let tail = map.split_at_nth(n);
+                map.check_invariants();
+                tail.check_invariants();
+
+                map.concat(tail);
+                map.check_invariants();
+                assert!(map.iter().map(|(k, _)| *k).eq(0..len));
+            }
+        }
+
+        // Trees of different heights are grafted on either side.
+        let lens = [0, 1, 2, CAP as u32, CAP as u32 * 3, 100, 2000];
+        for left_len in lens {
+            for right_len in lens {
+                let mut map = map_of(0..left_len);
+                map.concat(map_of(left_len..left_len + right_len));
+                map.check_invariants();
+                assert!(map.iter().map(|(k, _)| *k).eq(0..left_len + right_len));
+            }
+        }
+    }
+
+    check::<4>();
+    check::<5>();
+    check::<6>();
+    check::<7>();
+    check::<16>();
+}
+
+#[test]
+fn split_at_nth_partitions_by_index() {
+    let source: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i * 3, i)).collect();
+
+    for n in [0, 1, 2, 3, 4, 5, 6, 333, 500, 997, 999, 1000, 5000] {
+        let mut map = source.clone();
+        let other = map.split_at_nth(n);
+        let n = n.min(1000);
+
+        map.check_invariants();
+        other.check_invariants();
+        assert_eq!(map.len(), n);
+        assert_eq!(other.len(), 1000 - n);
+        assert!(map.iter().map(|(_, v)| *v as usize).eq(0..n));
+        assert!(other.iter().map(|(_, v)| *v as usize).eq(n..1000));
+        assert!(other
+            .iter()
+            .rev()
+            .map(|(_, v)| *v as usize)
+            .eq((n..1000).rev()));
+        assert_eq!(
+            other.peek_first_key().map(|key| key as usize),
+            (n < 1000).then_some(n * 3)
+        );
+    }
+
+    // Pages of equal size.
+    let mut rest = source.clone();
+    let mut pages = Vec::new();
+    while !rest.is_empty() {
+        let tail = rest.split_at_nth(64);
+        pages.push(core::mem::replace(&mut rest, tail));
+    }
+    assert_eq!(pages.len(), 16);
+    assert!(pages[..15].iter().all(|page| page.len() == 64));
+    assert!(pages
+        .iter()
+        .flat_map(|page| page.iter().map(|(_, v)| *v))
+        .eq(0..1000));
+}
+
+#[test]
+fn append_disjoint_matches_merge_path() {
+    for (left_len, right_len) in [(1, 1000), (1000, 1), (37, 4000), (4000, 4000)] {
+        let left: BTreeMap<u32, u32, 5> = (0..left_len).map(|i| (i, i)).collect();
+        let right: BTreeMap<u32, u32, 5> =
+            (left_len..left_len + right_len).map(|i| (i, i)).collect();
+
+        // The `right` starts after the end of the `left`, so only this order splices the leafs.
+        let (mut spliced, mut other) = (left.clone(), right.clone());
+        spliced.append(&mut other);
+        let (mut merged, mut other) = (right, left);
+        merged.append(&mut other);
+
+        spliced.check_invariants();
+        merged.check_invariants();
+        assert_eq!(spliced, merged);
+        assert!(spliced.keys().zip(0..).all(|(k, e)| *k == e));
+    }
+}
+
+#[test]
+fn reverse_iter_after_removals_merge_leaves() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..3000).map(|i| (i, i)).collect();
+    let leaves = map.stats().leaf_count;
+
+    // Removing most of the keys of every leaf merges it into its neighbors.
+    for key in (0..3000).filter(|k| k % 10 != 0) {
+        map.remove(&key);
+        if key % 97 == 0 {
+            let forward: Vec<u32> = map.iter().map(|(k, _)| *k).collect();
+            assert!(map
+                .iter()
+                .rev()
+                .map(|(k, _)| *k)
+                .eq(forward.into_iter().rev()));
+        }
+    }
+    map.check_invariants();
+    assert!(map.stats().leaf_count < leaves / 5);
+
+    assert!(map
+        .iter()
+        .rev()
+        .map(|(k, _)| *k)
+        .eq((0..300).rev().map(|k| k * 10)));
+    assert!(map
+        .range(1005..2005)
+        .rev()
+        .map(|(k, _)| *k)
+        .eq((101..201).rev().map(|k| k * 10)));
+    let mut cursor = map.upper_bound_cursor(&u32::MAX);
+    let mut count = 1;
+    while cursor.peek_prev().is_some() {
+        cursor.move_prev();
+        count += 1;
+    }
+    assert_eq!(cursor.key_value().map(|(k, _)| *k), Some(0));
+    assert_eq!(count, 300);
+}
+
+#[test]
+fn map_values_keeps_keys_and_leaves() {
+    let mut map: BTreeMap<String, u32, 5> = BTreeMap::new();
+    for i in 0..1000 {
+        map.insert(format!("{:04}", i * 7919 % 1000), i);
+    }
+    map.retain(|_, v| *v % 3 != 0);
+    let total: u32 = map.iter().map(|(_, v)| *v).sum();
+
+    let ratios = map.map_values(|v| f64::from(*v) / f64::from(total));
+    ratios.check_invariants();
+    assert_eq!(ratios.len(), map.len());
+    assert!(map
+        .iter()
+        .zip(ratios.iter())
+        .all(|((k, v), (rk, r))| *k == *rk && *r == f64::from(*v) / f64::from(total)));
+    assert!(ratios
+        .iter()
+        .rev()
+        .map(|(k, _)| k.clone())
+        .eq(map.iter().rev().map(|(k, _)| k.clone())));
+
+    let (stats, ratio_stats) = (map.stats(), ratios.stats());
+    assert_eq!(ratio_stats.leaf_count, stats.leaf_count);
+    assert_eq!(ratio_stats.total_entry_slots, stats.total_entry_slots);
+
+    let empty: BTreeMap<u32, u32, 5> = BTreeMap::new();
+    assert!(empty.map_values(|v| *v + 1).is_empty());
+}
+
+#[test]
+fn into_sorted_vec_is_sorted() {
+    let map: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i * 7919 % 1000, i)).collect();
+    let len = map.len();
+
+    let entries = map.into_sorted_vec();
+    assert_eq!(entries.len(), len);
+    assert!(entries.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(entries.iter().all(|&(k, v)| v * 7919 % 1000 == k));
+
+    assert!(BTreeMap::<u32, u32, 5>::new().into_sorted_vec().is_empty());
+}
+
+#[test]
+fn remove_single_entry_to_empty() {
+    let mut map: BTreeMap<u32, u32, 5> = BTreeMap::new();
+    for _ in 0..3 {
+        assert_eq!(map.insert(7, 7), None);
+        assert_eq!(map.len(), 1);
+
+        assert_eq!(map.remove(&7), Some(7));
+        map.check_invariants();
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.height(), 0);
+        assert_eq!(map.remove(&7), None);
+        assert_eq!(map.pop_first(), None);
+        assert_eq!(map.pop_last(), None);
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.iter().count(), 0);
+    }
+}
+
+#[test]
+fn append_overlap_links_one_chain() {
+    // Interleaved keys, so that no leaf of either map survives the merge as is.
+    let mut map: BTreeMap<u32, u32, 5> = (0..1500).map(|i| (i * 3, 0)).collect();
+    let mut other: BTreeMap<u32, u32, 5> = (0..1000).map(|i| (i * 2 + 1000, 1)).collect();
+    let expected: std::collections::BTreeSet<u32> =
+        map.iter().chain(other.iter()).map(|(k, _)| *k).collect();
+
+    map.append(&mut other);
+    map.check_invariants();
+    assert_eq!(map.len(), expected.len());
+    assert!(map.keys().zip(expected.iter()).all(|(k, e)| *k == *e));
+    assert_eq!(map.iter().count(), expected.len());
+    assert!(map
+        .keys()
+        .rev()
+        .zip(expected.iter().rev())
+        .all(|(k, e)| *k == *e));
+    assert_eq!(map.iter().rev().count(), expected.len());
+    assert!(map
+        .range(900..3100)
+        .rev()
+        .map(|(k, _)| *k)
+        .eq(expected.range(900..3100).rev().copied()));
+
+    // The emptied map doesn't keep any link into the merged leafs.
+    assert!(other.is_empty());
+    other.insert(7, 7);
+    other.check_invariants();
+    assert!(other.iter().rev().map(|(k, _)| *k).eq([7]));
+}
+
+#[test]
+fn append_matches_std_semantics() {
+    let build = |keys: &[u32], tag: u32| {
+        let mut map: BTreeMap<u32, (u32, u32), 5> = BTreeMap::new();
+        let mut std_map = std::collections::BTreeMap::new();
+        for &key in keys {
+            map.insert(key, (key, tag));
+            std_map.insert(key, (key, tag));
+        }
+        (map, std_map)
+    };
+
+    let evens: Vec<u32> = (0..500).map(|i| i * 2).collect();
+    let odds: Vec<u32> = (0..500).map(|i| i * 2 + 1).collect();
+    let high: Vec<u32> = (1000..1500).collect();
+    let cases: [(&[u32], &[u32]); 5] = [
+        (&evens, &odds),
+        (&evens, &evens[100..300]),
+        (&evens, &high),
+        (&high, &evens),
+        (&[], &odds),
+    ];
+
+    for (left, right) in cases {
+        let (mut map, mut std_map) = build(left, 0);
+        let (mut other, mut std_other) = build(right, 1);
+
+        map.append(&mut other);
+        std_map.append(&mut std_other);
+
+        assert!(other.is_empty());
+        assert_eq!(map.len(), std_map.len());
+        assert!(map
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .eq(std_map.iter().map(|(k, v)| (*k, *v))));
+    }
+}
+
+#[test]
+fn for_each_mut_visits_in_order() {
+    let mut map: BTreeMap<u32, u64, 5> = (0..1000).map(|i| (i * 7919 % 1000, 0)).collect();
+
+    let mut visited = Vec::new();
+    map.for_each_mut(|key, value| {
+        visited.push(*key);
+        *value = u64::from(*key) * 3 + 1;
+    });
+    assert!(visited.iter().copied().eq(0..1000));
+    assert!(map.iter().all(|(k, v)| *v == u64::from(*k) * 3 + 1));
+
+    map.for_each_mut(|_, value| *value -= 1);
+    assert!(map.iter().all(|(k, v)| *v == u64::from(*k) * 3));
+    map.check_invariants();
+
+    BTreeMap::<u32, u64, 5>::new().for_each_mut(|_, _| unreachable!());
+}
+
+#[test]
+fn retain_even_keys() {
+    let mut map: BTreeMap<u32, u32, 5> = BTreeMap::new();
+    for key in (0..100_000).map(|i| i * 7919 % 100_000) {
+        map.insert(key, 0);
+    }
+
+    let mut visited = vec![];
+    map.retain(|key, value| {
+        visited.push(*key);
+        *value = *key;
+        key % 2 == 0
+    });
+
+    map.check_invariants();
+    assert!(visited.iter().copied().eq(0..100_000));
+    assert_eq!(map.len(), 50_000);
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq((0..50_000).map(|i| (i * 2, i * 2))));
+}
+
+#[test]
+fn retain_collapsing_leaves_keeps_prev_links() {
+    let mut map: BTreeMap<u32, u32, 5> = (0..100_000).map(|i| (i * 7919 % 100_000, i)).collect();
+    let mut expected: Vec<u32> = (0..100_000).collect();
+
+    // Each round collapses most of the leafs, which are merged and relinked.
+    for modulo in [3, 7, 2, 5] {
+        map.retain(|key, _| key % modulo == 0);
+        expected.retain(|key| key % modulo == 0);
+
+        map.check_invariants();
+        assert_eq!(map.iter().rev().count(), expected.len());
+        assert!(map
+            .keys()
+            .rev()
+            .zip(expected.iter().rev())
+            .all(|(k, e)| *k == *e));
+    }
+
+    // Same with `extract_if`, which removes the entries one by one through the `remove`.
+    map.extract_if(|key, _| key % 4 != 0).for_each(drop);
+    expected.retain(|key| key % 4 == 0);
+
+    map.check_invariants();
+    assert_eq!(map.iter().rev().count(), expected.len());
+    assert!(map
+        .keys()
+        .rev()
+        .zip(expected.iter().rev())
+        .all(|(k, e)| *k == *e));
+}
+
+#[test]
+fn collect_bulk_loads_sorted_prefix() {
+    let map: BTreeMap<u32, u32, 5> = (0..10_000).map(|i| (i, i)).collect();
+    map.check_invariants();
+    assert_eq!(map.len(), 10_000);
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq((0..10_000).map(|i| (i, i))));
+    // Bulk loaded leafs are filled up, unlike the ones split by the inserts.
+    assert!(map.stats().fill_ratio > 0.99);
+
+    // Sorted prefix followed by the out of order and the duplicate keys.
+    let entries = (0..5000).chain((0..5000).rev()).chain(4990..6000);
+    let map: BTreeMap<u32, u32, 5> = entries
+        .clone()
+        .enumerate()
+        .map(|(i, k)| (k, i as u32))
+        .collect();
+    let expected: std::collections::BTreeMap<u32, u32> =
+        entries.enumerate().map(|(i, k)| (k, i as u32)).collect();
+    map.check_invariants();
+    assert!(map.iter().map(|(k, v)| (*k, *v)).eq(expected.into_iter()));
+}
+
+#[test]
+fn collect_sorts_and_dedups() {
+    let pairs: Vec<(u32, u32)> = (0..2000).map(|i| (i * 7919 % 1000, i)).collect();
+    let map: BTreeMap<_, _, 5> = pairs.iter().copied().collect();
+    let expected: std::collections::BTreeMap<_, _> = pairs.into_iter().collect();
+
+    assert_eq!(map.len(), 1000);
+    assert!(map.iter().map(|(k, v)| (*k, *v)).eq(expected.into_iter()));
+}
+
+#[test]
+fn extend_overwrites_existing_keys() {
+    let mut map = BTreeMap::<u32, u32, 5>::new();
+    map.extend((0..300).map(|i| (i, 0)));
+    map.extend((200..500).map(|i| (i, 1)));
+    let late: std::collections::BTreeMap<u32, u32> = (400..600).map(|i| (i, 2)).collect();
+    map.extend(late.iter());
+
+    assert_eq!(map.len(), 600);
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq((0..600).map(|i| (i, [0, 0, 1, 1, 2, 2][i as usize / 100]))));
+}
+
+#[test]
+fn keep_first_and_last_n() {
+    let nums: Vec<u32> = std::iter::repeat_with(rand::random).take(4096).collect();
+    let mut sorted = nums.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+
+    for n in [
+        0,
+        1,
+        2,
+        3,
+        7,
+        100,
+        1000,
+        sorted.len() - 1,
+        sorted.len(),
+        10000,
+    ] {
+        let mut first = BTreeMap::<_, _, 5>::new();
+        let mut last = BTreeMap::<_, _, 5>::new();
+        for &num in &nums {
+            first.insert(num, num);
+            last.insert(num, num);
+        }
+
+        first.keep_first_n(n);
+        last.keep_last_n(n);
+
+        let kept = n.min(sorted.len());
+        assert_eq!(first.len(), kept);
+        assert_eq!(last.len(), kept);
+        assert!(first
+            .iter()
+            .map(|(k, _)| *k)
+            .eq(sorted[..kept].iter().copied()));
+        assert!(last
+            .iter()
+            .map(|(k, _)| *k)
+            .eq(sorted[sorted.len() - kept..].iter().copied()));
+
+        for &num in &nums {
+            assert_eq!(first.remove(&num).is_some(), sorted[..kept].contains(&num));
+        }
+        assert!(first.is_empty());
     }
 }
@@ -0,0 +1,152 @@
+use alloc::vec::Vec;
+use core::cmp::Ord;
+
+use crate::checked::{self, Ref, RefKey, RefMut};
+use crate::node::{Node, Route, Target};
+use crate::BTreeMap;
+
+/// Position of a key in the tree, found by `BTreeMap::locate`.
+///
+/// It mutably borrows the map so any structural change
+/// invalidates it at compile time.
+#[derive(Debug)]
+pub struct Location<'a, K, V, const CAP: usize> {
+    map: &'a mut BTreeMap<K, V, CAP>,
+    path: Vec<usize>,
+    slot: Result<usize, usize>,
+}
+
+impl<'a, K: Ord, V, const CAP: usize> Location<'a, K, V, CAP> {
+    pub(crate) fn new(
+        map: &'a mut BTreeMap<K, V, CAP>,
+        path: Vec<usize>,
+        slot: Result<usize, usize>,
+    ) -> Self {
+        Location { map, path, slot }
+    }
+
+    /// Returns `true` if the located key exists in the map.
+    #[inline]
+    pub fn is_occupied(&self) -> bool {
+        self.slot.is_ok()
+    }
+
+    /// Returns the number of the entries before this location.
+    pub(crate) fn rank(&self) -> usize {
+        let idx = self.slot.unwrap_or_else(|idx| idx);
+        self.map
+            .root
+            .as_ref()
+            .map_or(0, |root| root.rank_at(&self.path, idx))
+    }
+
+    /// Returns the entry at this location, if occupied.
+    #[inline]
+    pub fn get(&self) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        let idx = self.slot.ok()?;
+        let root = self.map.root.as_ref()?;
+        Some(root.get_at(&self.path, idx))
+    }
+
+    /// Returns the value at this location mutably, if occupied.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<RefMut<'_, V>> {
+        let idx = self.slot.ok()?;
+        let leaf = self.map.root.as_mut()?.leaf_at_mut(&self.path);
+        Some(checked::map_mut(leaf.entries_mut(), |entries| {
+            &mut entries[idx].1
+        }))
+    }
+
+    /// Same as `get_mut`, but borrows the value for the whole lifetime of the map borrow.
+    pub(crate) fn into_mut(self) -> Option<RefMut<'a, V>> {
+        let idx = self.slot.ok()?;
+        let leaf = self.map.root.as_mut()?.leaf_at_mut(&self.path);
+        Some(checked::map_mut(leaf.entries_mut(), |entries| {
+            &mut entries[idx].1
+        }))
+    }
+
+    /// Converts the location into the entry borrowed for the lifetime of the map borrow, if occupied.
+    pub(crate) fn into_entry_mut(self) -> Option<(RefKey<'a, K>, RefMut<'a, V>)> {
+        let idx = self.slot.ok()?;
+        let leaf = self.map.root.as_mut()?.leaf_at_mut(&self.path);
+        Some(leaf.select_mut(idx))
+    }
+
+    /// Inserts the entry at this vacant location and returns its value,
+    /// without descending the tree again either to insert or to find the inserted value.
+    pub(crate) fn insert_vacant(self, key: K, value: V) -> RefMut<'a, V> {
+        let idx = match self.slot {
+            Err(idx) => idx,
+            Ok(_) => checked::unreachable!("location should be vacant"),
+        };
+        // Leafs are never dropped on insertion, and a split of this leaf
+        // moves the entries after the split point into its next leaf.
+        let leaf = self
+            .map
+            .root
+            .as_mut()
+            .map(|root| root.leaf_at_mut(&self.path).downgrade());
+        self.map
+            .insert_routed((key, value), Route::Path(&self.path, self.slot));
+
+        let map: &'a BTreeMap<K, V, CAP> = self.map;
+        let leaf = match leaf {
+            // SAFETY: The leaf belongs to the tree which is exclusively borrowed for `'a`.
+            Some(leaf) => unsafe { leaf.deref() },
+            None => map.root.as_ref().map(|root| root.head().leaf_ref()),
+        };
+        let leaf = leaf.unwrap_or_else(|| checked::unreachable!("leaf shouldn't be dropped"));
+
+        let len = leaf.entries().len();
+        let (leaf, idx) = match idx.checked_sub(len) {
+            None => (leaf, idx),
+            Some(idx) => match leaf.next() {
+                Some(next) => (next, idx),
+                None => checked::unreachable!("split leaf should have the next leaf"),
+            },
+        };
+        // SAFETY: The map is exclusively borrowed for `'a`, and no other guard of the leaf is alive.
+        checked::map_mut(unsafe { leaf.entries_mut() }, |entries| &mut entries[idx].1)
+    }
+
+    /// Removes the entry at this location through the normal rebalancing, if occupied.
+    pub(crate) fn remove(self) -> Option<(K, V)> {
+        let idx = self.slot.ok()?;
+        let n = self.map.root.as_ref()?.rank_at(&self.path, idx);
+        let (key, value, _) = self.map.remove_target(Target::<K>::Nth(n))?;
+        Some((key, value))
+    }
+
+    /// Inserts the entry at this location without descending the tree again,
+    /// returning the replaced entry if occupied.
+    ///
+    /// The `key` must be equal to the query used to locate it.
+    #[inline]
+    pub fn insert(self, key: K, value: V) -> Option<(K, V)> {
+        let route = Route::Path(&self.path, self.slot);
+        self.map.insert_routed((key, value), route)
+    }
+}
+
+#[test]
+fn insert_at_location_matches_insert() {
+    let mut m1 = BTreeMap::<_, _, 5>::new();
+    let mut m2 = BTreeMap::<_, _, 5>::new();
+
+    let nums: Vec<u16> = std::iter::repeat_with(rand::random).take(4096).collect();
+    for &n in &nums {
+        let location = m1.locate(&n);
+        let expected = location.get().map(|(k, v)| (*k, *v));
+        assert_eq!(location.is_occupied(), expected.is_some());
+        assert_eq!(location.insert(n, n as u32 + 1), expected);
+
+        m2.insert(n, n as u32 + 1);
+        assert_eq!(
+            format!("{:?}", m1.debug_tree()),
+            format!("{:?}", m2.debug_tree())
+        );
+    }
+    assert_eq!(m1.len(), m2.len());
+}
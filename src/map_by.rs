@@ -0,0 +1,420 @@
+use core::cmp::Ordering;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::RangeBounds;
+
+use crate::checked::{self, Ref, RefMut};
+use crate::{BTreeMap, Entry};
+
+/// Ordering of the keys of the `BTreeMapBy`, which replaces the `Ord` of the key type.
+///
+/// It's a type level function rather than a stored closure,
+/// so the map doesn't need to thread it through every node operation.
+pub trait Comparator<K: ?Sized> {
+    fn cmp(lhs: &K, rhs: &K) -> Ordering;
+}
+
+/// Orders the keys by their `Ord` impl.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Natural;
+
+/// Orders the keys by the reverse of their `Ord` impl.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Reverse;
+
+impl<K: Ord + ?Sized> Comparator<K> for Natural {
+    #[inline]
+    fn cmp(lhs: &K, rhs: &K) -> Ordering {
+        lhs.cmp(rhs)
+    }
+}
+
+impl<K: Ord + ?Sized> Comparator<K> for Reverse {
+    #[inline]
+    fn cmp(lhs: &K, rhs: &K) -> Ordering {
+        rhs.cmp(lhs)
+    }
+}
+
+/// Orders the floating point keys by their `total_cmp`.
+///
+/// A blanket key ordering trait over `K: Ord` can't also be implemented for `f64`,
+/// so floats are stored as is with this comparator instead.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TotalOrder;
+
+impl Comparator<f32> for TotalOrder {
+    #[inline]
+    fn cmp(lhs: &f32, rhs: &f32) -> Ordering {
+        lhs.total_cmp(rhs)
+    }
+}
+
+impl Comparator<f64> for TotalOrder {
+    #[inline]
+    fn cmp(lhs: &f64, rhs: &f64) -> Ordering {
+        lhs.total_cmp(rhs)
+    }
+}
+
+/// Key which is ordered by the comparator `C`.
+#[repr(transparent)]
+struct ByKey<K, C> {
+    key: K,
+    _comparator: PhantomData<fn() -> C>,
+}
+
+impl<K, C> ByKey<K, C> {
+    fn new(key: K) -> Self {
+        ByKey {
+            key,
+            _comparator: PhantomData,
+        }
+    }
+
+    fn from_ref(key: &K) -> &Self {
+        // SAFETY: `ByKey` is a `repr(transparent)` wrapper of the `K`.
+        unsafe { &*(key as *const K as *const Self) }
+    }
+}
+
+impl<K, C: Comparator<K>> PartialEq for ByKey<K, C> {
+    fn eq(&self, other: &Self) -> bool {
+        C::cmp(&self.key, &other.key) == Ordering::Equal
+    }
+}
+
+impl<K, C: Comparator<K>> Eq for ByKey<K, C> {}
+
+impl<K, C: Comparator<K>> PartialOrd for ByKey<K, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K, C: Comparator<K>> Ord for ByKey<K, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        C::cmp(&self.key, &other.key)
+    }
+}
+
+/// `BTreeMap` which orders its keys by the comparator `C` instead of the `K: Ord`.
+///
+/// ```
+/// use bpt::{BTreeMapBy, Reverse};
+///
+/// let mut map = BTreeMapBy::<u32, &str, Reverse, 5>::new();
+/// map.insert(1, "one");
+/// map.insert(2, "two");
+/// assert_eq!(*map.first_key_value().unwrap().0, 2);
+/// ```
+pub struct BTreeMapBy<K, V, C, const CAP: usize> {
+    inner: BTreeMap<ByKey<K, C>, V, CAP>,
+}
+
+impl<K, V, C: Comparator<K>, const CAP: usize> BTreeMapBy<K, V, C, CAP> {
+    /// Creates an empty map.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same condition as the `BTreeMap::new`.
+    #[inline]
+    pub fn new() -> Self {
+        BTreeMapBy {
+            inner: BTreeMap::new(),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    #[inline]
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.inner.insert(ByKey::new(key), value)
+    }
+
+    #[inline]
+    pub fn get(&self, key: &K) -> Option<Ref<'_, V>> {
+        self.inner.get(ByKey::from_ref(key))
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, key: &K) -> Option<RefMut<'_, V>> {
+        self.inner.get_mut(ByKey::from_ref(key))
+    }
+
+    #[inline]
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.inner.contains_key(ByKey::from_ref(key))
+    }
+
+    #[inline]
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.inner.remove(ByKey::from_ref(key))
+    }
+
+    #[inline]
+    pub fn remove_entry(&mut self, key: &K) -> Option<(K, V)> {
+        let (key, value) = self.inner.remove_entry(ByKey::from_ref(key))?;
+        Some((key.key, value))
+    }
+
+    /// Returns the first entry in the order of the comparator.
+    #[inline]
+    pub fn first_key_value(&self) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        let (key, value) = self.inner.first_key_value()?;
+        Some((checked::map_ref(key, |key| &key.key), value))
+    }
+
+    /// Returns the last entry in the order of the comparator.
+    #[inline]
+    pub fn last_key_value(&self) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        let (key, value) = self.inner.last_key_value()?;
+        Some((checked::map_ref(key, |key| &key.key), value))
+    }
+
+    /// Iterates the entries in the order of the comparator.
+    #[inline]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (Ref<'_, K>, Ref<'_, V>)> {
+        self.inner
+            .iter()
+            .map(|(key, value)| (checked::map_ref(key, |key| &key.key), value))
+    }
+
+    #[inline]
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = Ref<'_, K>> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Iterates the entries whose keys are within the `range` in the order of the comparator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the start of the `range` is greater than its end in the order of the comparator.
+    #[inline]
+    pub fn range<R: RangeBounds<K>>(
+        &self,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = (Ref<'_, K>, Ref<'_, V>)> {
+        let start = range.start_bound().map(ByKey::from_ref);
+        let end = range.end_bound().map(ByKey::from_ref);
+        self.inner
+            .range::<ByKey<K, C>, _>((start, end))
+            .map(|(key, value)| (checked::map_ref(key, |key| &key.key), value))
+    }
+
+    /// Returns the entry of the `key`, same as the `BTreeMap::entry`.
+    #[inline]
+    pub fn entry(&self, key: &K) -> Option<(Ref<'_, K>, Ref<'_, V>)> {
+        let (key, value) = self.inner.entry(ByKey::from_ref(key))?;
+        Some((checked::map_ref(key, |key| &key.key), value))
+    }
+
+    /// Gets the entry of the `key` for in-place manipulation, same as the `BTreeMap::entry_at`.
+    #[inline]
+    pub fn entry_at(&mut self, key: K) -> EntryBy<'_, K, V, C, CAP> {
+        EntryBy {
+            inner: self.inner.entry_at(ByKey::new(key)),
+        }
+    }
+}
+
+/// Entry of the `BTreeMapBy` for in-place manipulation.
+pub struct EntryBy<'a, K, V, C: Comparator<K>, const CAP: usize> {
+    inner: Entry<'a, ByKey<K, C>, V, CAP>,
+}
+
+impl<'a, K, V, C: Comparator<K>, const CAP: usize> EntryBy<'a, K, V, C, CAP> {
+    /// Returns `true` if the key exists in the map.
+    #[inline]
+    pub fn is_occupied(&self) -> bool {
+        matches!(self.inner, Entry::Occupied(_))
+    }
+
+    /// Inserts the `default` if vacant, and returns the value.
+    #[inline]
+    pub fn or_insert(self, default: V) -> RefMut<'a, V> {
+        self.inner.or_insert(default)
+    }
+
+    /// Inserts the value returned by the `default` if vacant, and returns the value.
+    #[inline]
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> RefMut<'a, V> {
+        self.inner.or_insert_with(default)
+    }
+
+    /// Calls the `f` with the value if occupied.
+    #[inline]
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        EntryBy {
+            inner: self.inner.and_modify(f),
+        }
+    }
+}
+
+impl<'a, K, V: Default, C: Comparator<K>, const CAP: usize> EntryBy<'a, K, V, C, CAP> {
+    /// Inserts the default value if vacant, and returns the value.
+    #[inline]
+    pub fn or_default(self) -> RefMut<'a, V> {
+        self.inner.or_default()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C: Comparator<K>, const CAP: usize> fmt::Debug
+    for EntryBy<'_, K, V, C, CAP>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntryBy")
+            .field("occupied", &self.is_occupied())
+            .finish()
+    }
+}
+
+impl<K, V, C: Comparator<K>, const CAP: usize> Default for BTreeMapBy<K, V, C, CAP> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, C: Comparator<K>, const CAP: usize> fmt::Debug
+    for BTreeMapBy<K, V, C, CAP>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, C: Comparator<K>, const CAP: usize> FromIterator<(K, V)> for BTreeMapBy<K, V, C, CAP> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = BTreeMapBy::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+#[test]
+fn reverse_comparator_iterates_descending() {
+    let mut map: BTreeMapBy<u32, u32, Reverse, 5> = (0..1000)
+        .map(|i| (i * 7919 % 1000, i * 7919 % 1000))
+        .collect();
+
+    assert_eq!(map.len(), 1000);
+    assert!(map.keys().zip((0..1000).rev()).all(|(k, e)| *k == e));
+    assert!(map.keys().rev().zip(0..1000).all(|(k, e)| *k == e));
+    assert_eq!(*map.first_key_value().unwrap().0, 999);
+    assert_eq!(*map.last_key_value().unwrap().0, 0);
+
+    for i in (0..1000).filter(|i| i % 2 == 0) {
+        assert!(map.remove(&i).is_some());
+    }
+    assert!(!map.contains_key(&500));
+    assert_eq!(*map.get(&501).unwrap(), 501);
+    assert!(map
+        .keys()
+        .zip((0..1000).rev().filter(|i| i % 2 == 1))
+        .all(|(k, e)| *k == e));
+}
+
+#[test]
+fn case_insensitive_comparator() {
+    struct IgnoreCase;
+
+    impl Comparator<&str> for IgnoreCase {
+        fn cmp(lhs: &&str, rhs: &&str) -> Ordering {
+            let lhs = lhs.bytes().map(|b| b.to_ascii_lowercase());
+            let rhs = rhs.bytes().map(|b| b.to_ascii_lowercase());
+            lhs.cmp(rhs)
+        }
+    }
+
+    let mut map = BTreeMapBy::<&str, u32, IgnoreCase, 5>::new();
+    assert_eq!(map.insert("Hello", 1), None);
+    assert_eq!(map.insert("world", 2), None);
+    assert_eq!(map.insert("HELLO", 3), Some(1));
+    assert_eq!(*map.get(&"hello").unwrap(), 3);
+    // `insert` replaces the key as well, same as the `BTreeMap::insert`.
+    assert_eq!(format!("{:?}", map), r#"{"HELLO": 3, "world": 2}"#);
+}
+
+#[test]
+fn float_keys_by_total_order() {
+    let mut map = BTreeMapBy::<f64, u32, TotalOrder, 5>::new();
+    for (i, key) in [2.5, -0.0, f64::INFINITY, 0.0, -1.0, f64::NAN, 1e-300]
+        .into_iter()
+        .enumerate()
+    {
+        assert_eq!(map.insert(key, i as u32), None);
+    }
+
+    let keys: Vec<f64> = map.iter().map(|(k, _)| *k).collect();
+    assert_eq!(keys[..6], [-1.0, -0.0, 0.0, 1e-300, 2.5, f64::INFINITY]);
+    assert!(keys[6].is_nan());
+    assert_eq!(*map.get(&-0.0).unwrap(), 1);
+    assert_eq!(*map.get(&0.0).unwrap(), 3);
+    assert_eq!(map.remove(&f64::NAN), Some(5));
+    assert_eq!(map.len(), 6);
+}
+
+#[test]
+// The ranges are in the order of the comparator, which is reversed.
+#[allow(clippy::reversed_empty_ranges)]
+fn range_follows_the_comparator() {
+    let map: BTreeMapBy<u32, u32, Reverse, 5> = (0..100).map(|i| (i, i * 2)).collect();
+
+    let keys: Vec<u32> = map.range(70..=40).map(|(k, _)| *k).collect();
+    assert_eq!(keys, (40..=70).rev().collect::<Vec<_>>());
+    let keys: Vec<u32> = map.range(50..40).rev().map(|(k, _)| *k).collect();
+    assert_eq!(keys, (41..=50).collect::<Vec<_>>());
+    assert!(map.range(..).map(|(k, _)| *k).eq((0..100).rev()));
+    assert!(map.range(..3).map(|(k, _)| *k).eq((4..100).rev()));
+    assert_eq!(map.range(10..10).count(), 0);
+    assert_eq!(
+        map.range(10..=10).map(|(_, v)| *v).collect::<Vec<_>>(),
+        [20]
+    );
+}
+
+#[test]
+#[should_panic]
+fn range_panics_on_start_after_end_by_the_comparator() {
+    let map: BTreeMapBy<u32, u32, Reverse, 5> = (0..100).map(|i| (i, i)).collect();
+    map.range(40..70).for_each(drop);
+}
+
+#[test]
+fn entry_follows_the_comparator() {
+    struct IgnoreCase;
+
+    impl Comparator<String> for IgnoreCase {
+        fn cmp(lhs: &String, rhs: &String) -> Ordering {
+            lhs.to_ascii_lowercase().cmp(&rhs.to_ascii_lowercase())
+        }
+    }
+
+    let mut map = BTreeMapBy::<String, u32, IgnoreCase, 5>::new();
+    for word in "the Quick brown fox jumps over THE lazy dog The".split(' ') {
+        *map.entry_at(word.into()).or_default() += 1;
+    }
+    assert_eq!(map.len(), 8);
+
+    let entry = map.entry(&"tHe".into()).map(|(k, v)| (k.clone(), *v));
+    assert_eq!(entry, Some(("the".into(), 3)));
+    assert!(map.entry(&"cat".into()).is_none());
+
+    assert!(map.entry_at("QUICK".into()).is_occupied());
+    let value = map.entry_at("QUICK".into()).and_modify(|v| *v += 10);
+    assert_eq!(*value.or_insert(0), 11);
+    assert!(!map.entry_at("cat".into()).is_occupied());
+    assert_eq!(*map.entry_at("cat".into()).or_insert_with(|| 7), 7);
+    assert_eq!(*map.get(&"Cat".into()).unwrap(), 7);
+}
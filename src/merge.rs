@@ -0,0 +1,150 @@
+use core::cmp::{Ord, Ordering};
+use core::iter::Peekable;
+
+use crate::checked::Ref;
+use crate::iter::Iter;
+use crate::BTreeMap;
+
+/// Which of the two maps contain the key yielded by `MergeJoin`.
+#[derive(Debug)]
+pub enum MergeSide<'a, V, W> {
+    Left(Ref<'a, V>),
+    Right(Ref<'a, W>),
+    Both(Ref<'a, V>, Ref<'a, W>),
+}
+
+/// Iterator walking two maps in lockstep, created by `BTreeMap::merge_join` or `merge_join`.
+pub struct MergeJoin<'a, K, V, W, const CAP: usize> {
+    left: Peekable<Iter<'a, K, V, CAP>>,
+    right: Peekable<Iter<'a, K, W, CAP>>,
+}
+
+impl<'a, K, V, W, const CAP: usize> MergeJoin<'a, K, V, W, CAP> {
+    pub(crate) fn new(left: Iter<'a, K, V, CAP>, right: Iter<'a, K, W, CAP>) -> Self {
+        MergeJoin {
+            left: left.peekable(),
+            right: right.peekable(),
+        }
+    }
+}
+
+/// Iterates the union of the keys of the two maps in ascending order,
+/// along with which of the two maps contain each key.
+///
+/// Same as `left.merge_join(right)`.
+#[inline]
+pub fn merge_join<'a, K: Ord, V, W, const CAP: usize>(
+    left: &'a BTreeMap<K, V, CAP>,
+    right: &'a BTreeMap<K, W, CAP>,
+) -> MergeJoin<'a, K, V, W, CAP> {
+    left.merge_join(right)
+}
+
+impl<'a, K: Ord, V, W, const CAP: usize> Iterator for MergeJoin<'a, K, V, W, CAP> {
+    type Item = (Ref<'a, K>, MergeSide<'a, V, W>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ord = match (self.left.peek(), self.right.peek()) {
+            (Some((l, _)), Some((r, _))) => (**l).cmp(&**r),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => return None,
+        };
+
+        Some(match ord {
+            Ordering::Less => {
+                let (key, value) = self.left.next()?;
+                (key, MergeSide::Left(value))
+            }
+            Ordering::Greater => {
+                let (key, value) = self.right.next()?;
+                (key, MergeSide::Right(value))
+            }
+            Ordering::Equal => {
+                let (key, left) = self.left.next()?;
+                let (_, right) = self.right.next()?;
+                (key, MergeSide::Both(left, right))
+            }
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (left, _) = self.left.size_hint();
+        let (right, _) = self.right.size_hint();
+        (left.max(right), Some(left + right))
+    }
+}
+
+#[cfg(test)]
+fn sides<const CAP: usize>(
+    left: &BTreeMap<u32, u32, CAP>,
+    right: &BTreeMap<u32, u32, CAP>,
+) -> Vec<(u32, Option<u32>, Option<u32>)> {
+    merge_join(left, right)
+        .map(|(k, side)| match side {
+            MergeSide::Left(v) => (*k, Some(*v), None),
+            MergeSide::Right(w) => (*k, None, Some(*w)),
+            MergeSide::Both(v, w) => (*k, Some(*v), Some(*w)),
+        })
+        .collect()
+}
+
+#[test]
+fn merge_join_disjoint_identical_and_overlapping() {
+    let low: BTreeMap<u32, u32, 5> = (0..100).map(|i| (i, i)).collect();
+    let high: BTreeMap<u32, u32, 5> = (100..200).map(|i| (i, i + 1)).collect();
+    let mid: BTreeMap<u32, u32, 5> = (50..150).map(|i| (i, i + 2)).collect();
+
+    let expected: Vec<_> = (0..100)
+        .map(|i| (i, Some(i), None))
+        .chain((100..200).map(|i| (i, None, Some(i + 1))))
+        .collect();
+    assert_eq!(sides(&low, &high), expected);
+
+    let expected: Vec<_> = (0..100).map(|i| (i, Some(i), Some(i))).collect();
+    assert_eq!(sides(&low, &low), expected);
+
+    let expected: Vec<_> = (0..50)
+        .map(|i| (i, Some(i), None))
+        .chain((50..100).map(|i| (i, Some(i), Some(i + 2))))
+        .chain((100..150).map(|i| (i, None, Some(i + 2))))
+        .collect();
+    assert_eq!(sides(&low, &mid), expected);
+
+    let empty = BTreeMap::<u32, u32, 5>::new();
+    assert!(sides(&empty, &empty).is_empty());
+}
+
+#[test]
+fn merge_join_classifies_like_std() {
+    let mut s1 = std::collections::BTreeMap::new();
+    let mut s2 = std::collections::BTreeMap::new();
+    let mut m1 = BTreeMap::<_, _, 5>::new();
+    let mut m2 = BTreeMap::<_, _, 5>::new();
+
+    for _ in 0..4096 {
+        let (n1, n2) = (rand::random::<u16>() % 8192, rand::random::<u16>() % 8192);
+        s1.insert(n1, n1 as u32);
+        m1.insert(n1, n1 as u32);
+        s2.insert(n2, n2 as i64);
+        m2.insert(n2, n2 as i64);
+    }
+
+    let expected: Vec<_> = s1
+        .keys()
+        .chain(s2.keys())
+        .collect::<std::collections::BTreeSet<_>>()
+        .into_iter()
+        .map(|k| (*k, s1.get(k).copied(), s2.get(k).copied()))
+        .collect();
+    let merged: Vec<_> = m1
+        .merge_join(&m2)
+        .map(|(k, side)| match side {
+            MergeSide::Left(v) => (*k, Some(*v), None),
+            MergeSide::Right(w) => (*k, None, Some(*w)),
+            MergeSide::Both(v, w) => (*k, Some(*v), Some(*w)),
+        })
+        .collect();
+
+    assert_eq!(merged, expected);
+}
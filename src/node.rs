@@ -1,8 +1,125 @@
-use std::borrow::Borrow;
-use std::cmp::Ord;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use arrayvec::ArrayVec;
+use core::alloc::Layout;
+use core::borrow::Borrow;
+use core::cmp::Ord;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
 
 use crate::checked::{Ref, RefKey, RefMut};
+use crate::internal::Internal;
 use crate::leaf::Leaf;
+use crate::AllocError;
+
+/// How to pick the child on each level of the descent.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Route<'a> {
+    /// Compare the keys on every level.
+    Search,
+    /// Follow the child indices recorded by a previous descent,
+    /// ending at the given slot of the leaf.
+    Path(&'a [usize], Result<usize, usize>),
+}
+
+impl<'a> Route<'a> {
+    pub fn descend(self, search: impl FnOnce() -> usize) -> (usize, Self) {
+        match self {
+            Route::Search => (search(), Route::Search),
+            Route::Path(path, slot) => match path.split_first() {
+                Some((&idx, rest)) => (idx, Route::Path(rest, slot)),
+                None => crate::checked::unreachable!("path is shorter than the tree"),
+            },
+        }
+    }
+}
+
+/// Nodes allocated ahead of an insertion, so it can't fail halfway after modifying the tree.
+/// The nodes not reserved are allocated on demand, aborting on failure.
+pub(crate) struct Spare<K, V, const CAP: usize> {
+    leaf: Option<Leaf<K, V, CAP>>,
+    internals: Vec<Box<MaybeUninit<Internal<K, V, CAP>>>>,
+}
+
+impl<K, V, const CAP: usize> Spare<K, V, CAP> {
+    /// Reserves nothing, which never fails.
+    pub fn new() -> Self {
+        Spare {
+            leaf: None,
+            internals: Vec::new(),
+        }
+    }
+
+    /// Allocates a leaf if the `leaf` is set, and the given number of internal nodes.
+    pub fn reserve(leaf: bool, internals: usize) -> Result<Self, AllocError> {
+        let mut spare = Self::new();
+        spare
+            .internals
+            .try_reserve_exact(internals)
+            .map_err(|_| AllocError)?;
+        for _ in 0..internals {
+            spare.internals.push(try_new_uninit()?);
+        }
+        if leaf {
+            spare.leaf = Some(Leaf::try_from_entries(ArrayVec::new())?);
+        }
+        Ok(spare)
+    }
+
+    /// Whether every reserved node is taken.
+    pub fn is_empty(&self) -> bool {
+        self.leaf.is_none() && self.internals.is_empty()
+    }
+
+    pub fn leaf(&mut self, entries: ArrayVec<(K, V), CAP>) -> Leaf<K, V, CAP> {
+        match self.leaf.take() {
+            Some(mut leaf) => {
+                *leaf.entries_mut() = entries;
+                leaf
+            }
+            None => Leaf::from_entries(entries),
+        }
+    }
+
+    pub fn internal(&mut self, node: Internal<K, V, CAP>) -> Box<Internal<K, V, CAP>> {
+        match self.internals.pop() {
+            Some(uninit) => Box::write(uninit, node),
+            None => Box::new(node),
+        }
+    }
+}
+
+/// Same as the `Box::new_uninit`, but returns `AllocError` instead of aborting.
+fn try_new_uninit<T>() -> Result<Box<MaybeUninit<T>>, AllocError> {
+    let layout = Layout::new::<T>();
+    // SAFETY: Nodes are never zero-sized.
+    let ptr = unsafe { alloc::alloc::alloc(layout) }.cast::<MaybeUninit<T>>();
+    let ptr = NonNull::new(ptr).ok_or(AllocError)?;
+    // SAFETY: The `ptr` is allocated by the global allocator with the layout of the `T`,
+    // and the `MaybeUninit` doesn't need to be initialized.
+    Ok(unsafe { Box::from_raw(ptr.as_ptr()) })
+}
+
+/// Which entry to remove.
+#[derive(Debug)]
+pub(crate) enum Target<'a, Q> {
+    /// The entry with the key equal to the query.
+    Key(&'a Q),
+    /// The entry with the smallest key.
+    First,
+    /// The entry with the largest key.
+    Last,
+    /// The `n`-th smallest entry, which must be less than the `len`.
+    Nth(usize),
+}
+
+impl<'a, Q> Clone for Target<'a, Q> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, Q> Copy for Target<'a, Q> {}
 
 pub(crate) trait Node<K, V, const CAP: usize>: Sized {
     fn head(&self) -> &Leaf<K, V, CAP>;
@@ -13,19 +130,69 @@ pub(crate) trait Node<K, V, const CAP: usize>: Sized {
 
     fn tail_mut(&mut self) -> &mut Leaf<K, V, CAP>;
 
+    /// Number of the entries in this subtree.
+    fn len(&self) -> usize;
+
+    /// Number of the entries whose key is less than the query.
+    fn rank<Q: Ord>(&self, query: &Q) -> usize
+    where
+        K: Borrow<Q>;
+
+    /// Number of the leading entries whose key satisfies the monotone `pred`.
+    fn partition_point<F: FnMut(&K) -> bool>(&self, pred: &mut F) -> usize;
+
+    /// The `n`-th smallest entry, which must be less than the `len`.
+    fn select(&self, n: usize) -> (Ref<'_, K>, Ref<'_, V>);
+
     fn get<Q: Ord>(&self, query: &Q) -> Option<(Ref<'_, K>, Ref<'_, V>)>
     where
         K: Borrow<Q>;
 
+    /// Same as the `select`, but borrows the value mutably.
+    fn select_mut(&mut self, n: usize) -> (RefKey<'_, K>, RefMut<'_, V>);
+
     fn get_mut<Q: Ord>(&mut self, query: &Q) -> Option<(RefKey<'_, K>, RefMut<'_, V>)>
     where
         K: Borrow<Q>;
 
-    fn insert(&mut self, new_entry: (K, V)) -> (Option<(K, V)>, Option<Self>);
+    fn get_at(&self, path: &[usize], idx: usize) -> (Ref<'_, K>, Ref<'_, V>);
+
+    fn leaf_at_mut(&mut self, path: &[usize]) -> &mut Leaf<K, V, CAP>;
+
+    fn locate<Q: Ord>(&self, query: &Q, path: &mut Vec<usize>) -> Result<usize, usize>
+    where
+        K: Borrow<Q>;
+
+    /// Same as the `locate`, but finds the `n`-th smallest entry which must be less than the `len`.
+    fn locate_nth(&self, n: usize, path: &mut Vec<usize>) -> usize;
+
+    /// Number of the entries before the slot `idx` of the leaf at the `path`.
+    fn rank_at(&self, path: &[usize], idx: usize) -> usize;
 
-    fn remove<Q: Ord>(&mut self, query: &Q) -> Option<((K, V), bool)>
+    /// Inserts the entry, taking the nodes created by the splits from the `spare`.
+    fn insert(
+        &mut self,
+        new_entry: (K, V),
+        route: Route<'_>,
+        spare: &mut Spare<K, V, CAP>,
+    ) -> (Option<(K, V)>, Option<Self>);
+
+    fn remove<Q: Ord>(&mut self, target: Target<'_, Q>) -> Option<((K, V), bool)>
     where
         K: Borrow<Q>;
 
     fn balance_or_drain(&mut self, next: &mut Self, lacking_next: bool) -> bool;
+
+    /// Number of the entries of a leaf, or the children of an internal node.
+    fn occupancy(&self) -> usize;
+
+    /// Moves the entries or the children between two adjacent nodes so both are at least half full,
+    /// or moves all of the `next` into `self` if they fit into one node.
+    /// Returns `true` if the `next` is drained.
+    fn redistribute(&mut self, next: &mut Self) -> bool;
+
+    /// Splits off the entries from the `n`-th onward into a new node of the same height,
+    /// where `n` must be within `1..self.len()`. The nodes along the cut may be left underfull,
+    /// and the leafs at the cut are unlinked from each other.
+    fn split_off_nth(&mut self, n: usize) -> Self;
 }
@@ -0,0 +1,122 @@
+//! Parallel bulk loading with the `"rayon"` feature.
+//!
+//! The tree is cut into the bottom internal nodes, which are built on the worker threads
+//! from the entries through the leafs. The calling thread only links the neighboring subtrees
+//! and builds the few levels above them.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use arrayvec::ArrayVec;
+use rayon::prelude::*;
+
+use crate::internal::Internal;
+use crate::leaf::Leaf;
+use crate::BTreeMap;
+
+/// Subtree built on a worker thread, not linked to any other leaf yet.
+struct Subtree<K, V, const CAP: usize>(Box<Internal<K, V, CAP>>);
+
+// SAFETY: The leafs of the subtree are only referenced by the subtree itself,
+// from its nodes and the links between its own leafs. Sending it moves every `Rc`
+// and `Weak` which share the reference counts together, and no guard is alive.
+unsafe impl<K: Send, V: Send, const CAP: usize> Send for Subtree<K, V, CAP> {}
+
+/// Start of the `idx`th part when the `len` items are split into the `count` parts
+/// the same way as the `distribute` does.
+fn part_start(len: usize, count: usize, idx: usize) -> usize {
+    idx * (len / count) + idx.min(len % count)
+}
+
+impl<K, V, const CAP: usize> BTreeMap<K, V, CAP>
+where
+    K: Clone + Ord + Send + Sync,
+    V: Clone + Send + Sync,
+{
+    /// Builds the map from the entries sorted by the key without duplicates,
+    /// building the subtrees in parallel.
+    ///
+    /// The tree is identical to the one collected from the same entries sequentially.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entries are not strictly ascending by the key,
+    /// or on the same condition as the `new`.
+    pub fn par_from_sorted(slice: &[(K, V)]) -> Self {
+        let mut map = BTreeMap::new();
+        assert!(
+            slice.par_windows(2).all(|pair| pair[0].0 < pair[1].0),
+            "entries should be sorted by the key without duplicates"
+        );
+        if slice.is_empty() {
+            return map;
+        }
+
+        // Same leafs and bottom nodes as the `distribute` makes for the sequential bulk loading.
+        let len = slice.len();
+        let leaf_count = len.div_ceil(CAP);
+        let node_count = leaf_count.div_ceil(CAP);
+        let mut nodes: Vec<Subtree<K, V, CAP>> = (0..node_count)
+            .into_par_iter()
+            .map(|node_idx| {
+                let first = part_start(leaf_count, node_count, node_idx);
+                let last = part_start(leaf_count, node_count, node_idx + 1);
+                let mut leaves: ArrayVec<Leaf<K, V, CAP>, CAP> = (first..last)
+                    .map(|idx| {
+                        let start = part_start(len, leaf_count, idx);
+                        let end = part_start(len, leaf_count, idx + 1);
+                        Leaf::from_entries(slice[start..end].iter().cloned().collect())
+                    })
+                    .collect();
+                for idx in 1..leaves.len() {
+                    let (prev, next) = leaves.split_at_mut(idx);
+                    prev[idx - 1].link_next(&mut next[0]);
+                }
+                Subtree(Internal::from_leaves(leaves))
+            })
+            .collect();
+
+        for idx in 1..nodes.len() {
+            let (prev, next) = nodes.split_at_mut(idx);
+            prev[idx - 1].0.link_next(&mut next[0].0);
+        }
+        let nodes = nodes.into_iter().map(|Subtree(node)| node).collect();
+        map.root = Some(Internal::build_spine(nodes));
+        map.length = len;
+        map
+    }
+}
+
+#[test]
+fn par_from_sorted_matches_sequential() {
+    for len in [0, 1, 4, 5, 6, 25, 26, 1000, 100_003] {
+        let entries: Vec<(u32, String)> = (0..len).map(|i| (i * 3, i.to_string())).collect();
+
+        let parallel = BTreeMap::<_, _, 5>::par_from_sorted(&entries);
+        let sequential: BTreeMap<_, _, 5> = entries.iter().cloned().collect();
+
+        parallel.check_invariants();
+        assert!(parallel == sequential);
+        assert_eq!(
+            format!("{:?}", parallel.debug_tree()),
+            format!("{:?}", sequential.debug_tree())
+        );
+        assert!(parallel
+            .iter()
+            .rev()
+            .map(|(k, _)| *k)
+            .eq((0..len).rev().map(|i| i * 3)));
+    }
+
+    let entries: Vec<(u64, u64)> = (0..10_000).map(|i| (i, i)).collect();
+    let parallel = BTreeMap::<_, _, 16>::par_from_sorted(&entries);
+    let sequential: BTreeMap<_, _, 16> = entries.iter().copied().collect();
+    assert_eq!(parallel.stats().leaf_count, sequential.stats().leaf_count);
+    assert!(parallel == sequential);
+}
+
+#[test]
+#[should_panic(expected = "entries should be sorted by the key without duplicates")]
+fn par_from_sorted_panics_on_duplicates() {
+    BTreeMap::<u32, u32, 5>::par_from_sorted(&[(1, 1), (2, 2), (2, 3)]);
+}
@@ -0,0 +1,32 @@
+//! Re-exports of the commonly used types.
+//!
+//! Besides the maps and their entries, it brings the iterators and the guards
+//! they yield, so the signatures over them can be written without the full paths.
+//!
+//! ```
+//! use bpt::prelude::*;
+//!
+//! fn bump<const CAP: usize>(values: IterMut<'_, u32, u32, CAP>) {
+//!     for (_, mut value) in values {
+//!         *value += 1;
+//!     }
+//! }
+//!
+//! let mut map: BTreeMap<u32, u32, 5> = BTreeMap::new();
+//! map.insert(1, 10);
+//!
+//! if let Entry::Vacant(entry) = map.entry_at(2) {
+//!     entry.insert(20);
+//! }
+//! bump(map.iter_mut());
+//! assert_eq!(*map.get(&2).unwrap(), 21);
+//!
+//! let set: BTreeSet<u32, 5> = map.iter().map(|(k, _)| *k).collect();
+//! assert_eq!(set.len(), 2);
+//! ```
+
+pub use crate::checked::{Ref, RefKey, RefMut};
+pub use crate::entry_api::{Entry, OccupiedEntry, VacantEntry};
+pub use crate::iter::{Iter, IterMut, Keys, Range, RangeMut, Values};
+pub use crate::set::BTreeSet;
+pub use crate::BTreeMap;
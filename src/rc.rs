@@ -0,0 +1,247 @@
+//! Minimal `Rc` and `Weak` which can report the allocation failure,
+//! as the `Rc::try_new` of the standard library is not stable yet.
+//!
+//! Both `checked` and `unchecked` modules build their `RcCell` on top of this.
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ops::Deref;
+use core::ptr::{self, NonNull};
+
+use crate::AllocError;
+
+struct RcBox<T> {
+    strong: Cell<usize>,
+    /// Number of the `Weak`s, plus one held by all the `Rc`s together.
+    weak: Cell<usize>,
+    value: T,
+}
+
+/// The counts of the `RcBox`, borrowed without covering the value
+/// which may be already dropped or moved out while the `Weak`s are alive.
+struct Counts<'a> {
+    strong: &'a Cell<usize>,
+    weak: &'a Cell<usize>,
+}
+
+impl Counts<'_> {
+    /// # Safety
+    ///
+    /// The `ptr` must point to a live allocation of the `RcBox`.
+    unsafe fn of<'a, T>(ptr: NonNull<RcBox<T>>) -> Counts<'a> {
+        let ptr = ptr.as_ptr();
+        // SAFETY: Only the count fields are borrowed, which are valid until the deallocation.
+        unsafe {
+            Counts {
+                strong: &*ptr::addr_of!((*ptr).strong),
+                weak: &*ptr::addr_of!((*ptr).weak),
+            }
+        }
+    }
+}
+
+/// Increments the count, aborting on overflow like the `std::rc::Rc`
+/// as a wrapped count would free the box while it's still in use.
+fn increment(count: &Cell<usize>) {
+    match count.get().checked_add(1) {
+        Some(next) => count.set(next),
+        None => abort(),
+    }
+}
+
+/// Aborts the process, which the `core` can't do directly.
+#[cold]
+fn abort() -> ! {
+    // A panic while panicking aborts.
+    struct Abort;
+
+    impl Drop for Abort {
+        fn drop(&mut self) {
+            panic!("reference count overflow");
+        }
+    }
+
+    let _abort = Abort;
+    panic!("reference count overflow");
+}
+
+pub(crate) struct Rc<T> {
+    ptr: NonNull<RcBox<T>>,
+    _marker: PhantomData<RcBox<T>>,
+}
+
+pub(crate) struct Weak<T> {
+    ptr: NonNull<RcBox<T>>,
+}
+
+impl<T> Rc<T> {
+    pub fn new(value: T) -> Self {
+        match Self::try_new(value) {
+            Ok(this) => this,
+            Err(AllocError) => handle_alloc_error(Layout::new::<RcBox<T>>()),
+        }
+    }
+
+    pub fn try_new(value: T) -> Result<Self, AllocError> {
+        // SAFETY: The `RcBox` is never zero-sized as it has the counts.
+        let ptr = unsafe { alloc(Layout::new::<RcBox<T>>()) }.cast::<RcBox<T>>();
+        let ptr = NonNull::new(ptr).ok_or(AllocError)?;
+        // SAFETY: The `ptr` is just allocated with the layout of the `RcBox`.
+        unsafe {
+            ptr.as_ptr().write(RcBox {
+                strong: Cell::new(1),
+                weak: Cell::new(1),
+                value,
+            })
+        };
+
+        Ok(Rc {
+            ptr,
+            _marker: PhantomData,
+        })
+    }
+
+    fn counts(&self) -> Counts<'_> {
+        // SAFETY: The box is alive while any `Rc` points to it.
+        unsafe { Counts::of(self.ptr) }
+    }
+
+    pub fn downgrade(this: &Self) -> Weak<T> {
+        increment(this.counts().weak);
+        Weak { ptr: this.ptr }
+    }
+
+    pub fn try_unwrap(this: Self) -> Result<T, Self> {
+        if this.counts().strong.get() != 1 {
+            return Err(this);
+        }
+
+        let this = ManuallyDrop::new(this);
+        this.counts().strong.set(0);
+        // SAFETY: It was the only `Rc`, and the `Weak`s can't read the value
+        // once the strong count is zero.
+        let value = unsafe { ptr::read(ptr::addr_of!((*this.ptr.as_ptr()).value)) };
+        // Releases the weak count held by the `Rc`s.
+        drop(Weak { ptr: this.ptr });
+        Ok(value)
+    }
+}
+
+impl<T> Clone for Rc<T> {
+    fn clone(&self) -> Self {
+        increment(self.counts().strong);
+        Rc {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for Rc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The value is alive while any `Rc` points to it.
+        unsafe { &(*self.ptr.as_ptr()).value }
+    }
+}
+
+impl<T> Drop for Rc<T> {
+    fn drop(&mut self) {
+        let strong = self.counts().strong;
+        strong.set(strong.get() - 1);
+        if strong.get() != 0 {
+            return;
+        }
+
+        // SAFETY: It was the last `Rc`, so nothing else can access the value.
+        unsafe { ptr::drop_in_place(ptr::addr_of_mut!((*self.ptr.as_ptr()).value)) };
+        drop(Weak { ptr: self.ptr });
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Rc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Weak<T> {
+    fn counts(&self) -> Counts<'_> {
+        // SAFETY: The box itself is alive while any `Weak` points to it,
+        // though the value may be already dropped.
+        unsafe { Counts::of(self.ptr) }
+    }
+
+    pub fn upgrade(&self) -> Option<Rc<T>> {
+        let strong = self.counts().strong;
+        if strong.get() == 0 {
+            return None;
+        }
+
+        increment(strong);
+        Some(Rc {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn strong_count(&self) -> usize {
+        self.counts().strong.get()
+    }
+
+    /// Returns the pointer to the value, which is dangling if the strong count is zero.
+    pub fn as_ptr(&self) -> *const T {
+        // SAFETY: The box itself is alive while this `Weak` exists.
+        unsafe { ptr::addr_of!((*self.ptr.as_ptr()).value) }
+    }
+}
+
+impl<T> Drop for Weak<T> {
+    fn drop(&mut self) {
+        let weak = self.counts().weak;
+        weak.set(weak.get() - 1);
+        if weak.get() == 0 {
+            // SAFETY: No `Rc` or `Weak` points to the box anymore,
+            // and the value is already dropped or moved out.
+            unsafe { dealloc(self.ptr.as_ptr().cast(), Layout::new::<RcBox<T>>()) };
+        }
+    }
+}
+
+impl<T> fmt::Debug for Weak<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(Weak)")
+    }
+}
+
+#[test]
+fn counts_and_drops_like_std_rc() {
+    let counter = alloc::rc::Rc::new(());
+    let rc = Rc::new(alloc::rc::Rc::clone(&counter));
+    let weak = Rc::downgrade(&rc);
+
+    let rc2 = weak.upgrade().unwrap();
+    assert_eq!(weak.strong_count(), 2);
+    let rc = Rc::try_unwrap(rc).unwrap_err();
+    drop(rc2);
+    assert_eq!(weak.strong_count(), 1);
+    assert_eq!(alloc::rc::Rc::strong_count(&counter), 2);
+
+    drop(rc);
+    assert_eq!(weak.strong_count(), 0);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(alloc::rc::Rc::strong_count(&counter), 1);
+
+    let rc = Rc::new(alloc::rc::Rc::clone(&counter));
+    let weak = Rc::downgrade(&rc);
+    let value = Rc::try_unwrap(rc).unwrap();
+    assert!(weak.upgrade().is_none());
+    drop(weak);
+    assert_eq!(alloc::rc::Rc::strong_count(&counter), 2);
+    drop(value);
+    assert_eq!(alloc::rc::Rc::strong_count(&counter), 1);
+}
@@ -0,0 +1,99 @@
+//! Serde support with the `"serde"` feature.
+//!
+//! The map is serialized the same as the `std::collections::BTreeMap`,
+//! so the `CAP` doesn't appear in the serialized form.
+//! On deserialization the `CAP` is chosen by the target type instead.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::BTreeMap;
+
+impl<K: Ord + Serialize, V: Serialize, const CAP: usize> Serialize for BTreeMap<K, V, CAP> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self.iter() {
+            #[cfg_attr(feature = "unchecked", allow(clippy::needless_borrow))]
+            map.serialize_entry::<K, V>(&key, &value)?;
+        }
+        map.end()
+    }
+}
+
+/// Entries are inserted one by one as the wire format doesn't guarantee the order.
+/// Later entries overwrite the earlier ones with the same key.
+impl<'de, K, V, const CAP: usize> Deserialize<'de> for BTreeMap<K, V, CAP>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor(PhantomData))
+    }
+}
+
+struct MapVisitor<K, V, const CAP: usize>(PhantomData<BTreeMap<K, V, CAP>>);
+
+impl<'de, K, V, const CAP: usize> Visitor<'de> for MapVisitor<K, V, CAP>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = BTreeMap<K, V, CAP>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut map = BTreeMap::try_new().map_err(de::Error::custom)?;
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+#[test]
+fn serialize_matches_std_btreemap() {
+    let entries: Vec<(u32, String)> = (0..1000)
+        .map(|i| i * 7919 % 1000)
+        .map(|key| (key, key.to_string()))
+        .collect();
+    let map: BTreeMap<_, _, 5> = entries.iter().cloned().collect();
+    let expected: std::collections::BTreeMap<_, _> = entries.into_iter().collect();
+
+    assert_eq!(
+        serde_json::to_string(&map).unwrap(),
+        serde_json::to_string(&expected).unwrap()
+    );
+    assert_eq!(
+        serde_json::to_string(&BTreeMap::<String, u32, 5>::new()).unwrap(),
+        "{}"
+    );
+}
+
+#[test]
+fn deserialize_round_trips() {
+    let map: BTreeMap<u32, Vec<u32>, 5> = (0..1000)
+        .map(|i| i * 7919 % 1000)
+        .map(|key| (key, vec![key; (key % 3) as usize]))
+        .collect();
+    let json = serde_json::to_string(&map).unwrap();
+
+    let restored: BTreeMap<u32, Vec<u32>, 7> = serde_json::from_str(&json).unwrap();
+    assert!(restored == map);
+
+    let unsorted: BTreeMap<String, u32, 5> =
+        serde_json::from_str(r#"{"b": 1, "a": 2, "b": 3}"#).unwrap();
+    assert!(unsorted
+        .iter()
+        .map(|(k, v)| (k.clone(), *v))
+        .eq([("a".to_string(), 2), ("b".to_string(), 3)]));
+
+    let err = serde_json::from_str::<BTreeMap<u32, u32, 3>>("{}").unwrap_err();
+    assert!(err.to_string().contains("at least 4"), "{}", err);
+}
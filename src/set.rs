@@ -0,0 +1,205 @@
+use core::borrow::Borrow;
+use core::cmp::Ord;
+use core::fmt;
+
+use crate::checked::Ref;
+use crate::iter::Keys;
+use crate::merge::{MergeJoin, MergeSide};
+use crate::BTreeMap;
+
+/// Ordered set based on the `BTreeMap` with the `()` values.
+pub struct BTreeSet<T, const CAP: usize>(BTreeMap<T, (), CAP>);
+
+impl<T: Ord, const CAP: usize> BTreeSet<T, CAP> {
+    /// Creates an empty set.
+    ///
+    /// # Panics
+    ///
+    /// Panics on the same condition as the `BTreeMap::new`.
+    #[inline]
+    pub fn new() -> Self {
+        BTreeSet(BTreeMap::new())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Adds the value to the set, and returns `true` if it didn't exist.
+    ///
+    /// The existing value is kept untouched, same as the `std::collections::BTreeSet`.
+    #[inline]
+    pub fn insert(&mut self, value: T) -> bool {
+        self.0.try_insert(value, ()).is_ok()
+    }
+
+    /// Removes the value from the set, and returns `true` if it existed.
+    #[inline]
+    pub fn remove<Q>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord,
+    {
+        self.0.remove(value).is_some()
+    }
+
+    #[inline]
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord,
+    {
+        self.0.contains_key(value)
+    }
+
+    /// Returns the smallest value.
+    #[inline]
+    pub fn first(&self) -> Option<Ref<'_, T>> {
+        self.0.first_key_value().map(|(value, _)| value)
+    }
+
+    /// Returns the largest value.
+    #[inline]
+    pub fn last(&self) -> Option<Ref<'_, T>> {
+        self.0.last_key_value().map(|(value, _)| value)
+    }
+
+    /// Iterates the values in ascending order.
+    #[inline]
+    pub fn iter(&self) -> Keys<'_, T, (), CAP> {
+        self.0.keys()
+    }
+}
+
+macro_rules! set_ops {
+    ($($(#[$meta:meta])* $method:ident -> $name:ident { $($side:pat),* })*) => {
+        impl<T: Ord, const CAP: usize> BTreeSet<T, CAP> {$(
+            $(#[$meta])*
+            #[inline]
+            pub fn $method<'a>(&'a self, other: &'a Self) -> $name<'a, T, CAP> {
+                $name(self.0.merge_join(&other.0))
+            }
+        )*}
+
+        $(
+            #[doc = concat!("Lazy iterator created by `BTreeSet::", stringify!($method), "`.")]
+            pub struct $name<'a, T, const CAP: usize>(MergeJoin<'a, T, (), (), CAP>);
+
+            impl<'a, T: Ord, const CAP: usize> Iterator for $name<'a, T, CAP> {
+                type Item = Ref<'a, T>;
+
+                fn next(&mut self) -> Option<Self::Item> {
+                    self.0.find_map(|(value, side)| match side {
+                        $($side)|* => Some(value),
+                        #[allow(unreachable_patterns)]
+                        _ => None,
+                    })
+                }
+            }
+        )*
+    };
+}
+
+set_ops! {
+    /// Iterates the values in either set in ascending order, each value only once.
+    union -> Union { MergeSide::Left(_), MergeSide::Right(_), MergeSide::Both(..) }
+    /// Iterates the values in both sets in ascending order.
+    intersection -> Intersection { MergeSide::Both(..) }
+    /// Iterates the values in the `self` but not in the `other` in ascending order.
+    difference -> Difference { MergeSide::Left(_) }
+    /// Iterates the values in exactly one of the sets in ascending order.
+    symmetric_difference -> SymmetricDifference { MergeSide::Left(_), MergeSide::Right(_) }
+}
+
+impl<T: Ord, const CAP: usize> Default for BTreeSet<T, CAP> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord + Clone, const CAP: usize> Clone for BTreeSet<T, CAP> {
+    fn clone(&self) -> Self {
+        BTreeSet(self.0.clone())
+    }
+}
+
+impl<T: Ord + fmt::Debug, const CAP: usize> fmt::Debug for BTreeSet<T, CAP> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord, const CAP: usize> FromIterator<T> for BTreeSet<T, CAP> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut set = BTreeSet::new();
+        for value in iter {
+            set.insert(value);
+        }
+        set
+    }
+}
+
+#[test]
+fn insert_contains_remove() {
+    let mut set = BTreeSet::<u32, 5>::new();
+    assert!(set.is_empty());
+    assert!(set.first().is_none());
+
+    for i in (0..1000).map(|i| i * 7919 % 1000) {
+        assert!(set.insert(i));
+    }
+    assert!(!set.insert(500));
+    assert_eq!(set.len(), 1000);
+    assert!(set.contains(&999));
+    assert!(!set.contains(&1000));
+
+    for i in (0..1000).filter(|i| i % 3 == 0) {
+        assert!(set.remove(&i));
+    }
+    assert!(!set.remove(&0));
+    assert!(!set.contains(&300));
+    assert!(set.contains(&301));
+    assert_eq!(*set.first().unwrap(), 1);
+    assert_eq!(*set.last().unwrap(), 998);
+}
+
+#[test]
+fn iterates_in_order() {
+    let set: BTreeSet<u32, 5> = [5, 3, 9, 1, 3, 7].into_iter().collect();
+
+    assert_eq!(set.len(), 5);
+    assert!(set.iter().zip([1, 3, 5, 7, 9]).all(|(v, e)| *v == e));
+    assert!(set.iter().rev().zip([9, 7, 5, 3, 1]).all(|(v, e)| *v == e));
+    assert_eq!(format!("{:?}", set), "{1, 3, 5, 7, 9}");
+}
+
+#[test]
+fn set_algebra_of_small_sets() {
+    let a: BTreeSet<u32, 5> = [1, 2, 3, 5, 8, 13].into_iter().collect();
+    let b: BTreeSet<u32, 5> = [2, 3, 5, 7, 11, 13].into_iter().collect();
+    let collect = |iter: &mut dyn Iterator<Item = Ref<'_, u32>>| -> Vec<u32> {
+        let mut values = vec![];
+        for value in iter {
+            values.push(*value);
+        }
+        values
+    };
+
+    assert_eq!(collect(&mut a.union(&b)), [1, 2, 3, 5, 7, 8, 11, 13]);
+    assert_eq!(collect(&mut a.intersection(&b)), [2, 3, 5, 13]);
+    assert_eq!(collect(&mut a.difference(&b)), [1, 8]);
+    assert_eq!(collect(&mut b.difference(&a)), [7, 11]);
+    assert_eq!(collect(&mut a.symmetric_difference(&b)), [1, 7, 8, 11]);
+
+    let empty = BTreeSet::<u32, 5>::new();
+    assert_eq!(collect(&mut a.union(&empty)), [1, 2, 3, 5, 8, 13]);
+    assert!(a.intersection(&empty).next().is_none());
+    assert!(empty.difference(&a).next().is_none());
+}
@@ -0,0 +1,65 @@
+/// Structure and occupancy of a `BTreeMap`, returned by `BTreeMap::stats`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TreeStats {
+    /// Number of levels including the leafs, or 0 if the map is empty.
+    pub depth: usize,
+    /// Number of internal nodes, including the root.
+    pub internal_node_count: usize,
+    pub leaf_count: usize,
+    /// Number of entries the leafs can hold without splitting, `leaf_count * CAP`.
+    pub total_entry_slots: usize,
+    /// Ratio of the stored entries to the `total_entry_slots`, or 0 if the map is empty.
+    pub fill_ratio: f64,
+}
+
+impl TreeStats {
+    pub(crate) fn new(
+        height: usize,
+        internal_node_count: usize,
+        leaf_count: usize,
+        total_entry_slots: usize,
+        len: usize,
+    ) -> Self {
+        TreeStats {
+            depth: if leaf_count == 0 { 0 } else { height + 1 },
+            internal_node_count,
+            leaf_count,
+            total_entry_slots,
+            fill_ratio: if total_entry_slots == 0 {
+                0.0
+            } else {
+                len as f64 / total_entry_slots as f64
+            },
+        }
+    }
+}
+
+#[test]
+fn stats_of_populated_map() {
+    use crate::BTreeMap;
+
+    let empty = BTreeMap::<u32, u32, 5>::new();
+    let stats = empty.stats();
+    assert_eq!(stats.depth, 0);
+    assert_eq!(stats.leaf_count, 0);
+    assert_eq!(stats.fill_ratio, 0.0);
+
+    let mut depths = Vec::new();
+    for len in [10u32, 100, 1000, 10000] {
+        let map: BTreeMap<u32, u32, 5> = (0..len).map(|i| (i * 7919 % len, i)).collect();
+        let stats = map.stats();
+
+        // Every non-root leaf holds at least 3 of 5 slots.
+        assert!(stats.leaf_count * 3 <= len as usize);
+        assert!(stats.leaf_count * 5 >= len as usize);
+        assert_eq!(stats.total_entry_slots, stats.leaf_count * 5);
+        assert!(stats.fill_ratio >= 0.6 && stats.fill_ratio <= 1.0);
+        assert!(stats.internal_node_count < stats.leaf_count);
+        depths.push(stats.depth);
+    }
+
+    // Depth grows by about log_3..log_5 of 10 for each tenfold of the entries.
+    assert!(depths
+        .windows(2)
+        .all(|pair| pair[1] > pair[0] && pair[1] <= pair[0] + 3));
+}
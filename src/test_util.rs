@@ -0,0 +1,30 @@
+//! Fixtures shared by the unit tests.
+
+use core::cell::Cell;
+use core::cmp::Ordering;
+
+std::thread_local! {
+    static COMPARISONS: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Key which counts how many times it's compared on the current thread.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Counted(pub u32);
+
+impl PartialOrd for Counted {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Counted {
+    fn cmp(&self, other: &Self) -> Ordering {
+        COMPARISONS.with(|count| count.set(count.get() + 1));
+        self.0.cmp(&other.0)
+    }
+}
+
+/// Number of the comparisons of the `Counted` since the last call.
+pub(crate) fn comparisons() -> usize {
+    COMPARISONS.with(|count| count.replace(0))
+}
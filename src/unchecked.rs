@@ -0,0 +1,195 @@
+//! Drop-in replacement of the `checked` module without the runtime checks.
+//!
+//! Every borrow and assumption made through this module is verified
+//! by the `checked` module in the default build, so the soundness of this module
+//! follows from the correctness of the checked build.
+
+use core::cell::UnsafeCell;
+
+use crate::rc::{Rc, Weak};
+use crate::AllocError;
+
+// Code written for the guards of the checked build may need `mut` or `drop` which are
+// redundant on plain references, so those call sites allow the lints for this build only.
+pub type Ref<'a, T> = &'a T;
+pub type RefMut<'a, T> = &'a mut T;
+
+macro_rules! unchecked_assume {
+    ($cond:expr $(, $($arg:tt)*)?) => {
+        if !$cond {
+            unsafe { core::hint::unreachable_unchecked() }
+        }
+    };
+}
+
+macro_rules! unchecked_unreachable {
+    ($($arg:tt)*) => {
+        unsafe { core::hint::unreachable_unchecked() }
+    };
+}
+
+pub(crate) use unchecked_assume as assume;
+pub(crate) use unchecked_unreachable as unreachable;
+
+#[derive(Debug)]
+pub(super) struct RcCell<T> {
+    inner: Rc<UnsafeCell<T>>,
+}
+
+#[derive(Debug)]
+pub(super) struct WeakCell<T> {
+    inner: Weak<UnsafeCell<T>>,
+}
+
+/// Borrowed handle to the content of the `RcCell`.
+#[derive(Debug)]
+pub(super) struct CellRef<'a, T> {
+    inner: &'a UnsafeCell<T>,
+}
+
+impl<T> RcCell<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            inner: Rc::new(UnsafeCell::new(value)),
+        }
+    }
+
+    pub fn try_new(value: T) -> Result<Self, AllocError> {
+        Ok(Self {
+            inner: Rc::try_new(UnsafeCell::new(value))?,
+        })
+    }
+
+    pub fn get(&self) -> Ref<'_, T> {
+        unsafe { &*self.inner.get() }
+    }
+
+    pub fn cell_ref(&self) -> CellRef<'_, T> {
+        CellRef { inner: &self.inner }
+    }
+
+    pub fn try_unwrap(self) -> Result<T, Self> {
+        match Rc::try_unwrap(self.inner) {
+            Ok(cell) => Ok(cell.into_inner()),
+            Err(inner) => Err(Self { inner }),
+        }
+    }
+
+    // Methods below may allow to modify reference counts
+    // so they must take `&mut self` though the implementation doesn't requires it.
+
+    pub fn get_mut(&mut self) -> RefMut<'_, T> {
+        unsafe { &mut *self.inner.get() }
+    }
+
+    pub fn shallow_clone(&mut self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+
+    pub fn downgrade(&mut self) -> WeakCell<T> {
+        WeakCell {
+            inner: Rc::downgrade(&self.inner),
+        }
+    }
+}
+
+impl<T> WeakCell<T> {
+    /// Returns a strong handle to the pointee, or `None` if it's already dropped.
+    ///
+    /// It takes `&mut self` as it modifies the reference count, same as the `RcCell::shallow_clone`.
+    pub fn upgrade(&mut self) -> Option<RcCell<T>> {
+        Some(RcCell {
+            inner: self.inner.upgrade()?,
+        })
+    }
+    /// Follows the link without touching the reference counts.
+    /// Returns `None` if the pointee is already dropped.
+    ///
+    /// # Safety
+    ///
+    /// The pointee must be kept alive by some `RcCell` for the lifetime `'a`.
+    pub unsafe fn deref<'a>(&self) -> Option<CellRef<'a, T>> {
+        if self.inner.strong_count() == 0 {
+            return None;
+        }
+
+        Some(CellRef {
+            inner: unsafe { &*self.inner.as_ptr() },
+        })
+    }
+}
+
+impl<'a, T> Clone for CellRef<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for CellRef<'a, T> {}
+
+impl<'a, T> CellRef<'a, T> {
+    pub fn get(self) -> Ref<'a, T> {
+        unsafe { &*self.inner.get() }
+    }
+
+    /// # Safety
+    ///
+    /// The caller must have the exclusive access to the tree which owns the cell,
+    /// and mustn't hold any other guard of the same cell while the returned one is alive.
+    pub unsafe fn get_mut(self) -> RefMut<'a, T> {
+        // SAFETY: The caller guarantees that no other reference to the content is alive.
+        unsafe { &mut *self.inner.get() }
+    }
+
+    pub fn ptr_eq(self, rhs: Self) -> bool {
+        core::ptr::eq(self.inner, rhs.inner)
+    }
+}
+
+#[derive(Debug)]
+pub struct RefKey<'a, T>(&'a T);
+
+impl<'a, T> core::ops::Deref for RefKey<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<'a, T: core::fmt::Display> core::fmt::Display for RefKey<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+pub fn map_ref<T: ?Sized, U: ?Sized, F: FnOnce(&T) -> &U>(orig: Ref<'_, T>, f: F) -> Ref<'_, U> {
+    f(orig)
+}
+
+pub fn split_ref<T: ?Sized, U: ?Sized, V: ?Sized, F: FnOnce(&T) -> (&U, &V)>(
+    orig: Ref<'_, T>,
+    f: F,
+) -> (Ref<'_, U>, Ref<'_, V>) {
+    f(orig)
+}
+
+pub fn map_mut<T: ?Sized, U: ?Sized, F: FnOnce(&mut T) -> &mut U>(
+    orig: RefMut<'_, T>,
+    f: F,
+) -> RefMut<'_, U> {
+    f(orig)
+}
+
+pub fn split_mut<T: ?Sized, U: ?Sized, V: ?Sized, F: FnOnce(&mut T) -> (&mut U, &mut V)>(
+    orig: RefMut<'_, T>,
+    f: F,
+) -> (RefMut<'_, U>, RefMut<'_, V>) {
+    f(orig)
+}
+
+pub fn mut_to_key<T>(orig: RefMut<'_, T>) -> RefKey<'_, T> {
+    RefKey(orig)
+}
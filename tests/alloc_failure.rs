@@ -0,0 +1,79 @@
+//! Checks `try_insert_entry` reports the allocation failure instead of aborting.
+//!
+//! The global allocator is replaced for this test binary,
+//! so it has only one test to not fail the allocations of the others.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use bpt::{AllocError, BTreeMap};
+
+static FAIL: AtomicBool = AtomicBool::new(false);
+
+struct FailingAlloc;
+
+unsafe impl GlobalAlloc for FailingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match FAIL.load(Ordering::SeqCst) {
+            true => std::ptr::null_mut(),
+            false => unsafe { System.alloc(layout) },
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: FailingAlloc = FailingAlloc;
+
+/// Runs the `f` while every allocation fails.
+fn failing<T>(f: impl FnOnce() -> T) -> T {
+    FAIL.store(true, Ordering::SeqCst);
+    let result = f();
+    FAIL.store(false, Ordering::SeqCst);
+    result
+}
+
+#[test]
+fn try_insert_entry_reports_alloc_error() {
+    let mut map = BTreeMap::<u32, u32, 5>::new();
+    assert_eq!(failing(|| map.try_insert_entry(0, 0)), Err(AllocError));
+    assert!(map.is_empty());
+
+    for key in 0..4 {
+        map.insert(key * 2, key);
+    }
+    let results = failing(|| {
+        [
+            // The leaf has a spare slot.
+            map.try_insert_entry(7, 7),
+            // The leaf is full now, but replacing doesn't split it.
+            map.try_insert_entry(0, 10),
+            map.try_insert_entry(5, 5),
+        ]
+    });
+    assert_eq!(results, [Ok(None), Ok(Some((0, 0))), Err(AllocError)]);
+    map.check_invariants();
+    assert!(map
+        .iter()
+        .map(|(k, v)| (*k, *v))
+        .eq([(0, 10), (2, 1), (4, 2), (6, 3), (7, 7)]));
+
+    assert_eq!(map.try_insert_entry(5, 5), Ok(None));
+    map.check_invariants();
+    assert_eq!(map.len(), 6);
+
+    // Splits up to the root of a deeper tree only use the nodes reserved up front.
+    let mut map = BTreeMap::<u32, u32, 4>::new();
+    for key in (0..2000).rev() {
+        assert_eq!(map.try_insert_entry(key, key), Ok(None));
+    }
+    assert!(map.stats().depth >= 4);
+    for key in (0..2000).step_by(3) {
+        assert_eq!(map.try_insert_entry(key, 0), Ok(Some((key, key))));
+    }
+    map.check_invariants();
+    assert_eq!(map.len(), 2000);
+}
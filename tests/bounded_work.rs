@@ -0,0 +1,116 @@
+//! Checks the bulk operations only rebuild the nodes along the paths they cut,
+//! by counting the allocations they make.
+//!
+//! The counter is per thread, so the tests of this binary can run in parallel.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use bpt::BTreeMap;
+
+thread_local! {
+    static ALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.with(|allocs| allocs.set(allocs.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+/// Returns the result of the `f` and the number of the allocations it made.
+fn count_allocs<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = ALLOCS.with(Cell::get);
+    let result = f();
+    (result, ALLOCS.with(Cell::get) - before)
+}
+
+fn map_of(len: u32) -> BTreeMap<u32, u32, 5> {
+    (0..len).map(|i| (i, i)).collect()
+}
+
+#[test]
+fn remove_range_allocates_along_the_cuts() {
+    let mut map = map_of(100_000);
+    let depth = map.stats().depth;
+    assert!(map.stats().leaf_count >= 20_000);
+
+    for range in [40_000..40_001, 50_000..80_000, 0..10_000, 90_000..100_000] {
+        let (removed, allocs) = count_allocs(|| map.remove_range(range.clone()));
+        assert_eq!(removed, range.len());
+        // Each of the two cuts splits a node per level, and the join may add a root.
+        assert!(
+            allocs <= 2 * depth + 1,
+            "{} allocations for {:?}",
+            allocs,
+            range
+        );
+    }
+
+    map.check_invariants();
+    assert!(map
+        .iter()
+        .map(|(k, _)| *k)
+        .eq((10_000..40_000).chain(40_001..50_000).chain(80_000..90_000)));
+}
+
+#[test]
+fn keep_n_allocates_along_the_cut() {
+    let mut map = map_of(100_000);
+    let depth = map.stats().depth;
+
+    let ((), allocs) = count_allocs(|| map.keep_first_n(70_000));
+    assert!(allocs <= depth, "{} allocations", allocs);
+    let ((), allocs) = count_allocs(|| map.keep_last_n(50_000));
+    assert!(allocs <= depth, "{} allocations", allocs);
+
+    map.check_invariants();
+    assert!(map.iter().map(|(k, _)| *k).eq(20_000..70_000));
+}
+
+#[test]
+fn split_off_allocates_along_the_cut() {
+    let mut map = map_of(100_000);
+    let depth = map.stats().depth;
+
+    let (other, allocs) = count_allocs(|| map.split_off(&60_000));
+    assert!(allocs <= depth, "{} allocations", allocs);
+
+    map.check_invariants();
+    other.check_invariants();
+    assert!(map.iter().map(|(k, _)| *k).eq(0..60_000));
+    assert!(other.iter().map(|(k, _)| *k).eq(60_000..100_000));
+}
+
+#[test]
+fn append_disjoint_allocates_along_the_junction() {
+    for (left_len, right_len) in [
+        (100_000, 100_000),
+        (100_000, 7),
+        (7, 100_000),
+        (100_000, 1000),
+    ] {
+        let mut map = map_of(left_len);
+        let mut other: BTreeMap<u32, u32, 5> =
+            (left_len..left_len + right_len).map(|i| (i, i)).collect();
+        let depth = map.stats().depth.max(other.stats().depth);
+
+        let ((), allocs) = count_allocs(|| map.append(&mut other));
+        // Only the nodes on the junction path may split, and the root may grow.
+        assert!(allocs <= depth + 1, "{} allocations", allocs);
+
+        map.check_invariants();
+        assert!(other.is_empty());
+        assert!(map.iter().map(|(k, _)| *k).eq(0..left_len + right_len));
+    }
+}
@@ -0,0 +1,157 @@
+//! Runs the same operation sequences on `bpt::BTreeMap` and `std::collections::BTreeMap`.
+//!
+//! CI runs it both with and without the `"unchecked"` feature.
+//! Both builds must agree with the same reference step by step,
+//! which catches any divergence of the unchecked build from the checked one.
+
+// Guards are plain references with the `"unchecked"` feature.
+#![allow(unused_mut, clippy::needless_option_as_deref)]
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::ops::Bound::{Excluded, Unbounded};
+use std::ops::RangeBounds;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Checks the invariants on every step while the map is small,
+/// and periodically on the larger map to keep the test fast.
+fn check<const CAP: usize>(map: &bpt::BTreeMap<u32, u64, CAP>, len: usize, step: usize) {
+    if len <= 1024 || step.is_multiple_of(128) {
+        map.check_invariants();
+    }
+}
+
+/// Checks the `range` yields the same entries as the reference, in both directions.
+fn check_range<const CAP: usize, R: RangeBounds<u32> + Clone + Debug>(
+    map: &bpt::BTreeMap<u32, u64, CAP>,
+    reference: &BTreeMap<u32, u64>,
+    range: R,
+    step: usize,
+) {
+    let res = map.range(range.clone()).map(|(k, v)| (*k, *v));
+    assert!(
+        res.eq(reference.range(range.clone()).map(|(k, v)| (*k, *v))),
+        "range {:?}, step {}",
+        range,
+        step
+    );
+    let res = map.range(range.clone()).rev().map(|(k, v)| (*k, *v));
+    assert!(
+        res.eq(reference.range(range.clone()).rev().map(|(k, v)| (*k, *v))),
+        "range rev {:?}, step {}",
+        range,
+        step
+    );
+}
+
+fn run<const CAP: usize>(seed: u64, ops: usize, keys: u32) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut reference = BTreeMap::new();
+    let mut map = bpt::BTreeMap::<u32, u64, CAP>::new();
+
+    for step in 0..ops {
+        let key = rng.gen_range(0..keys);
+        match rng.gen_range(0..100) {
+            0..=39 => {
+                let value = rng.gen();
+                let res = map.insert(key, value);
+                assert_eq!(res, reference.insert(key, value), "insert, step {}", step);
+                check(&map, reference.len(), step);
+            }
+            40..=59 => {
+                let res = map.get(&key).as_deref().copied();
+                assert_eq!(res, reference.get(&key).copied(), "get, step {}", step);
+            }
+            60..=69 => {
+                let res = map.get_mut(&key).map(|mut value| {
+                    *value = value.wrapping_mul(3);
+                    *value
+                });
+                let expected = reference.get_mut(&key).map(|value| {
+                    *value = value.wrapping_mul(3);
+                    *value
+                });
+                assert_eq!(res, expected, "get_mut, step {}", step);
+            }
+            70..=89 => {
+                let res = map.remove_entry(&key);
+                assert_eq!(res, reference.remove_entry(&key), "remove, step {}", step);
+                check(&map, reference.len(), step);
+            }
+            90..=94 => {
+                let location = map.locate(&key);
+                let value = rng.gen();
+                let res = location.insert(key, value);
+                let expected = reference.insert(key, value).map(|prev| (key, prev));
+                assert_eq!(res, expected, "locate, step {}", step);
+            }
+            95..=97 => {
+                let end = rng.gen_range(key..keys);
+                check_range(&map, &reference, key..end, step);
+                check_range(&map, &reference, ..=end, step);
+                check_range(&map, &reference, (Excluded(key), Unbounded), step);
+            }
+            98 => {
+                let end = rng.gen_range(key..keys);
+                let res: Vec<_> = map
+                    .range_mut(key..=end)
+                    .map(|(k, mut v)| {
+                        *v = v.wrapping_add(1);
+                        (*k, *v)
+                    })
+                    .collect();
+                let expected: Vec<_> = reference
+                    .range_mut(key..=end)
+                    .map(|(k, v)| {
+                        *v = v.wrapping_add(1);
+                        (*k, *v)
+                    })
+                    .collect();
+                assert_eq!(res, expected, "range_mut, step {}", step);
+            }
+            _ => {
+                let res = map.iter().map(|(k, v)| (*k, *v));
+                assert!(
+                    res.eq(reference.iter().map(|(k, v)| (*k, *v))),
+                    "iter, step {}",
+                    step
+                );
+                let res = map.iter().rev().map(|(k, v)| (*k, *v));
+                assert!(
+                    res.eq(reference.iter().rev().map(|(k, v)| (*k, *v))),
+                    "iter rev, step {}",
+                    step
+                );
+            }
+        }
+        assert_eq!(map.len(), reference.len(), "len, step {}", step);
+    }
+
+    map.check_invariants();
+    assert!(map.into_iter().eq(reference));
+}
+
+macro_rules! parity_tests {
+    ($($name:ident: CAP = $cap:literal, seed = $seed:literal, ops = $ops:literal, keys = $keys:expr;)*) => {
+        $(
+            #[test]
+            fn $name() {
+                run::<$cap>($seed, $ops, $keys);
+            }
+        )*
+    };
+}
+
+parity_tests! {
+    parity_cap5_dense: CAP = 5, seed = 1, ops = 50_000, keys = 256;
+    parity_cap5_sparse: CAP = 5, seed = 2, ops = 50_000, keys = 1_000_000;
+    parity_cap15_dense: CAP = 15, seed = 3, ops = 50_000, keys = 4096;
+    parity_cap15_sparse: CAP = 15, seed = 4, ops = 50_000, keys = 1_000_000;
+    parity_cap31_dense: CAP = 31, seed = 5, ops = 50_000, keys = 8192;
+    parity_cap4_dense: CAP = 4, seed = 6, ops = 50_000, keys = 256;
+    parity_cap16_dense: CAP = 16, seed = 7, ops = 50_000, keys = 4096;
+    parity_cap16_sparse: CAP = 16, seed = 8, ops = 50_000, keys = 1_000_000;
+    parity_cap32_dense: CAP = 32, seed = 9, ops = 50_000, keys = 8192;
+}